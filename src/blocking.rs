@@ -0,0 +1,289 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Instant,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    commands::{self, Block, Context},
+    db::{Db, Value},
+    resp::RESP,
+};
+
+/// A blocking command that found nothing to serve, kept so that it can run
+/// again once one of its keys is ready.
+pub struct BlockedCommand {
+    pub args: Vec<Bytes>,
+    pub block: Block,
+    pub timeout_reply: RESP,
+}
+
+/// The clients parked by blocking commands, in the spirit of Redis'
+/// `blocked.c`. The keyspace keeps the waiters of each key, longest waiting
+/// first, so that creating one of those keys marks it ready; this keeps the
+/// command each client is waiting to run and the deadlines, soonest first.
+///
+/// Serving and timing out only work out which clients are done and with
+/// what reply. Sending the replies and running whatever the clients
+/// pipelined after the blocking command is up to the caller.
+#[derive(Default)]
+pub struct BlockedClients {
+    commands: HashMap<usize, BlockedCommand>,
+    timeouts: BTreeSet<(Instant, usize)>,
+}
+
+impl BlockedClients {
+    pub fn contains(&self, id: usize) -> bool {
+        self.commands.contains_key(&id)
+    }
+
+    /// The deadline of the client that times out first.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timeouts.first().map(|&(deadline, _)| deadline)
+    }
+
+    /// Runs a command for client `id`, returning its reply, or `None` if the
+    /// command blocked the client.
+    pub fn execute(&mut self, ctx: &mut Context, id: usize, args: Vec<Bytes>) -> Option<RESP> {
+        let reply = commands::dispatch(ctx, &args);
+        let Some(block) = ctx.block.take() else {
+            return Some(reply);
+        };
+        ctx.db.block_client(id, &block.keys);
+        if let Some(deadline) = block.deadline {
+            self.timeouts.insert((deadline, id));
+        }
+        self.commands.insert(
+            id,
+            BlockedCommand {
+                args,
+                block,
+                timeout_reply: reply,
+            },
+        );
+        None
+    }
+
+    /// Forgets client `id`, taking it off every key it was blocked on.
+    pub fn unblock(&mut self, db: &mut Db, id: usize) -> Option<BlockedCommand> {
+        let blocked = self.commands.remove(&id)?;
+        db.unblock_client(id, &blocked.block.keys);
+        if let Some(deadline) = blocked.block.deadline {
+            self.timeouts.remove(&(deadline, id));
+        }
+        Some(blocked)
+    }
+
+    /// Runs the commands of the clients blocked on keys that have been
+    /// created since, longest waiting client first, and returns the clients
+    /// that got served along with their replies. A client whose command
+    /// still finds nothing to serve, because the clients before it took
+    /// everything, stays blocked in its place.
+    pub fn serve(&mut self, ctx: &mut Context) -> Vec<(usize, RESP)> {
+        let mut served = Vec::new();
+        loop {
+            let ready = ctx.db.take_ready_keys();
+            if ready.is_empty() {
+                return served;
+            }
+
+            for key in ready {
+                for id in ctx.db.blocked_clients(&key) {
+                    let Some(value_type) = ctx.db.get(&key).map(Value::type_name) else {
+                        break;
+                    };
+                    let Some(blocked) = self.commands.get(&id) else {
+                        continue;
+                    };
+                    if blocked.block.value_type != value_type {
+                        continue;
+                    }
+
+                    let reply = commands::dispatch(ctx, &blocked.args);
+                    if ctx.block.take().is_some() {
+                        continue;
+                    }
+                    self.unblock(ctx.db, id);
+                    served.push((id, reply));
+                }
+            }
+        }
+    }
+
+    /// Unblocks the clients whose deadline is at or before `now`, returning
+    /// them along with their timeout replies.
+    pub fn time_out(&mut self, db: &mut Db, now: Instant) -> Vec<(usize, RESP)> {
+        let mut timed_out = Vec::new();
+        while let Some(&(deadline, id)) = self.timeouts.first() {
+            if deadline > now {
+                break;
+            }
+            match self.unblock(db, id) {
+                Some(blocked) => timed_out.push((id, blocked.timeout_reply)),
+                None => {
+                    self.timeouts.remove(&(deadline, id));
+                }
+            }
+        }
+        timed_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{commands::CommandTable, config::Config};
+
+    struct Server {
+        commands: CommandTable,
+        config: Config,
+        db: Db,
+        blocked: BlockedClients,
+    }
+
+    impl Server {
+        fn new() -> Server {
+            Server {
+                commands: CommandTable::new(),
+                config: Config::default(),
+                db: Db::default(),
+                blocked: BlockedClients::default(),
+            }
+        }
+
+        /// Runs a command for client `id` as the server would, returning
+        /// the wire encoding of its reply, or `None` if it blocked.
+        fn run(&mut self, id: usize, args: &[&str]) -> Option<Vec<u8>> {
+            let mut ctx = Context {
+                commands: &self.commands,
+                config: &self.config,
+                db: &mut self.db,
+                block: None,
+            };
+            let args = args
+                .iter()
+                .map(|arg| Bytes::copy_from_slice(arg.as_bytes()));
+            let reply = self.blocked.execute(&mut ctx, id, args.collect())?;
+            Some(encode(reply))
+        }
+
+        /// Serves the clients blocked on ready keys, returning who got
+        /// which reply in the order they were served.
+        fn serve(&mut self) -> Vec<(usize, Vec<u8>)> {
+            let mut ctx = Context {
+                commands: &self.commands,
+                config: &self.config,
+                db: &mut self.db,
+                block: None,
+            };
+            let served = self.blocked.serve(&mut ctx);
+            served
+                .into_iter()
+                .map(|(id, reply)| (id, encode(reply)))
+                .collect()
+        }
+    }
+
+    fn encode(reply: RESP) -> Vec<u8> {
+        let mut out = Vec::new();
+        reply.write_to(&mut out);
+        out
+    }
+
+    /// The reply BLPOP gives when it pops `element` from `key`.
+    fn popped(key: &str, element: &str) -> Vec<u8> {
+        format!(
+            "*2\r\n${}\r\n{key}\r\n${}\r\n{element}\r\n",
+            key.len(),
+            element.len()
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn serves_the_clients_of_a_key_in_the_order_they_blocked() {
+        let mut server = Server::new();
+        for id in [3, 1, 2] {
+            assert_eq!(server.run(id, &["BLPOP", "list", "0"]), None);
+        }
+
+        server.run(9, &["RPUSH", "list", "a", "b", "c"]);
+        assert_eq!(
+            server.serve(),
+            [
+                (3, popped("list", "a")),
+                (1, popped("list", "b")),
+                (2, popped("list", "c"))
+            ]
+        );
+        assert!(server.db.blocked_clients(b"list").is_empty());
+    }
+
+    #[test]
+    fn rechecks_the_key_after_each_client_it_serves() {
+        let mut server = Server::new();
+        server.run(1, &["BLPOP", "list", "0"]);
+        server.run(2, &["BLPOP", "list", "0"]);
+
+        // The first client empties the list, so the second stays blocked.
+        server.run(9, &["RPUSH", "list", "a"]);
+        assert_eq!(server.serve(), [(1, popped("list", "a"))]);
+        assert!(server.blocked.contains(2));
+        assert_eq!(server.db.blocked_clients(b"list"), [2]);
+
+        server.run(9, &["RPUSH", "list", "b"]);
+        assert_eq!(server.serve(), [(2, popped("list", "b"))]);
+        assert!(!server.blocked.contains(2));
+    }
+
+    #[test]
+    fn a_client_blocked_on_several_keys_leaves_all_of_them() {
+        let mut server = Server::new();
+        server.run(1, &["BLPOP", "first", "second", "third", "0"]);
+        server.run(2, &["BLPOP", "third", "0"]);
+
+        server.run(9, &["RPUSH", "second", "a"]);
+        server.run(9, &["RPUSH", "third", "b"]);
+        assert_eq!(
+            server.serve(),
+            [(1, popped("second", "a")), (2, popped("third", "b"))]
+        );
+        for key in [&b"first"[..], b"second", b"third"] {
+            assert!(server.db.blocked_clients(key).is_empty());
+        }
+    }
+
+    #[test]
+    fn ignores_keys_created_with_another_type() {
+        let mut server = Server::new();
+        server.run(1, &["BLPOP", "key", "0"]);
+        server.run(9, &["SET", "key", "value"]);
+        assert!(server.serve().is_empty());
+        assert!(server.blocked.contains(1));
+    }
+
+    #[test]
+    fn timing_out_replies_with_a_null() {
+        let mut server = Server::new();
+        server.run(1, &["BLPOP", "list", "0.01"]);
+        server.run(2, &["BLPOP", "list", "0"]);
+        let deadline = server
+            .blocked
+            .next_deadline()
+            .expect("client 1 has a deadline");
+
+        let early = deadline - Duration::from_millis(1);
+        assert!(server.blocked.time_out(&mut server.db, early).is_empty());
+        let timed_out = server.blocked.time_out(&mut server.db, deadline);
+        let timed_out: Vec<_> = timed_out
+            .into_iter()
+            .map(|(id, reply)| (id, encode(reply)))
+            .collect();
+        assert_eq!(timed_out, [(1, b"*-1\r\n".to_vec())]);
+        assert_eq!(server.blocked.next_deadline(), None);
+        assert_eq!(server.db.blocked_clients(b"list"), [2]);
+    }
+}
@@ -0,0 +1,454 @@
+use bytes::Bytes;
+
+use super::{
+    bitmap::{lookup_buffer_mut, lookup_bytes, parse_bit_offset, MAX_BIT_OFFSET},
+    parse_i64, Command, CommandError, CommandFlags, CommandResult, Context,
+};
+use crate::resp::RESP;
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("bitfield", bitfield, -2, CommandFlags::WRITE, 1, 1, 1),
+    Command::new(
+        "bitfield_ro",
+        bitfield_ro,
+        -2,
+        CommandFlags::READONLY.union(CommandFlags::FAST),
+        1,
+        1,
+        1,
+    ),
+];
+
+/// What to do when SET or INCRBY produce a value that does not fit the
+/// field.
+#[derive(Clone, Copy)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// A field type such as `i16` or `u8`.
+#[derive(Clone, Copy)]
+struct FieldType {
+    signed: bool,
+    bits: u32,
+}
+
+impl FieldType {
+    /// Signed fields can be up to 64 bits wide, unsigned ones up to 63 so
+    /// that every value still fits the integer reply.
+    fn parse(arg: &[u8]) -> Result<FieldType, CommandError> {
+        let field = match arg.split_first() {
+            Some((b'i' | b'I', bits)) => std::str::from_utf8(bits)
+                .ok()
+                .and_then(|bits| bits.parse().ok())
+                .filter(|bits| (1..=64).contains(bits))
+                .map(|bits| FieldType { signed: true, bits }),
+            Some((b'u' | b'U', bits)) => std::str::from_utf8(bits)
+                .ok()
+                .and_then(|bits| bits.parse().ok())
+                .filter(|bits| (1..=63).contains(bits))
+                .map(|bits| FieldType {
+                    signed: false,
+                    bits,
+                }),
+            _ => None,
+        };
+        field.ok_or(CommandError::Message(
+            "Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.",
+        ))
+    }
+
+    fn min(self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Fits `value` into the field according to `overflow`, or returns
+    /// `None` if it does not fit and the overflow mode is FAIL.
+    fn fit(self, value: i128, overflow: Overflow) -> Option<i64> {
+        if (self.min()..=self.max()).contains(&value) {
+            return Some(value as i64);
+        }
+        match overflow {
+            Overflow::Wrap => {
+                let modulus = 1i128 << self.bits;
+                let wrapped = value.rem_euclid(modulus);
+                Some(if wrapped > self.max() {
+                    wrapped - modulus
+                } else {
+                    wrapped
+                } as i64)
+            }
+            Overflow::Sat => Some(value.clamp(self.min(), self.max()) as i64),
+            Overflow::Fail => None,
+        }
+    }
+}
+
+enum Operation {
+    Get,
+    Set(i64),
+    IncrBy(i64),
+}
+
+struct Field {
+    operation: Operation,
+    field_type: FieldType,
+    offset: u64,
+    overflow: Overflow,
+}
+
+/// Parses a field offset, which is either a bit offset or, prefixed with `#`,
+/// an index that is multiplied by the field width.
+fn parse_field_offset(arg: &[u8], field_type: FieldType) -> Result<u64, CommandError> {
+    let Some(index) = arg.strip_prefix(b"#") else {
+        return parse_bit_offset(arg);
+    };
+    match parse_i64(index).map(|index| index.checked_mul(field_type.bits as i64)) {
+        Ok(Some(offset)) if (0..=MAX_BIT_OFFSET).contains(&offset) => Ok(offset as u64),
+        _ => Err(CommandError::Message(
+            "bit offset is not an integer or out of range",
+        )),
+    }
+}
+
+/// Parses the subcommands following the key. OVERFLOW applies to every SET
+/// and INCRBY after it.
+fn parse_fields(args: &[Bytes]) -> Result<Vec<Field>, CommandError> {
+    let mut fields = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    let mut i = 0;
+
+    while i < args.len() {
+        let subcommand = args[i].to_ascii_uppercase();
+        let operands = match subcommand.as_slice() {
+            b"OVERFLOW" => 1,
+            b"GET" => 2,
+            b"SET" | b"INCRBY" => 3,
+            _ => return Err(CommandError::Syntax),
+        };
+        if args.len() - i <= operands {
+            return Err(CommandError::Syntax);
+        }
+        let next = i + 1 + operands;
+        let operands = &args[i + 1..next];
+        i = next;
+
+        if subcommand == b"OVERFLOW" {
+            overflow = match operands[0].to_ascii_uppercase().as_slice() {
+                b"WRAP" => Overflow::Wrap,
+                b"SAT" => Overflow::Sat,
+                b"FAIL" => Overflow::Fail,
+                _ => return Err(CommandError::Message("Invalid OVERFLOW type specified")),
+            };
+            continue;
+        }
+
+        let field_type = FieldType::parse(&operands[0])?;
+        let offset = parse_field_offset(&operands[1], field_type)?;
+        let operation = match subcommand.as_slice() {
+            b"GET" => Operation::Get,
+            b"SET" => Operation::Set(parse_i64(&operands[2])?),
+            _ => Operation::IncrBy(parse_i64(&operands[2])?),
+        };
+
+        fields.push(Field {
+            operation,
+            field_type,
+            offset,
+            overflow,
+        });
+    }
+    Ok(fields)
+}
+
+/// Reads `field_type.bits` bits starting at bit `offset`, most significant
+/// bit first. Bits past the end of `bytes` read as zero.
+fn read_field(bytes: &[u8], offset: u64, field_type: FieldType) -> i64 {
+    let mut value: u64 = 0;
+    for bit in offset..offset + field_type.bits as u64 {
+        let byte = bytes.get((bit >> 3) as usize).copied().unwrap_or(0);
+        value = (value << 1) | ((byte >> (7 - (bit & 7))) & 1) as u64;
+    }
+    if field_type.signed && field_type.bits < 64 && value >> (field_type.bits - 1) != 0 {
+        value |= u64::MAX << field_type.bits;
+    }
+    value as i64
+}
+
+/// Writes the low `bits` bits of `value` starting at bit `offset`. The
+/// buffer must already be long enough.
+fn write_field(buffer: &mut [u8], offset: u64, bits: u32, value: i64) {
+    for i in 0..bits as u64 {
+        let bit = offset + i;
+        let mask = 1u8 << (7 - (bit & 7));
+        let byte = &mut buffer[(bit >> 3) as usize];
+        if (value as u64 >> (bits as u64 - 1 - i)) & 1 != 0 {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+    }
+}
+
+/// BITFIELD key [GET type offset] [SET type offset value]
+/// [INCRBY type offset increment] [OVERFLOW WRAP | SAT | FAIL] ...
+///
+/// SET replies with the old value and INCRBY with the new one. Both reply
+/// with a null, and leave the field alone, if the result overflows under
+/// OVERFLOW FAIL.
+fn bitfield(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let fields = parse_fields(&args[2..])?;
+    let writes_end = fields
+        .iter()
+        .filter(|field| !matches!(field.operation, Operation::Get))
+        .map(|field| (field.offset + field.field_type.bits as u64).div_ceil(8) as usize)
+        .max();
+    let Some(len) = writes_end else {
+        return get_fields(ctx, &args[1], &fields);
+    };
+
+    let buffer = lookup_buffer_mut(ctx, &args[1], len)?;
+    let mut replies = Vec::with_capacity(fields.len());
+    for field in &fields {
+        let Field {
+            field_type, offset, ..
+        } = *field;
+        let old = read_field(buffer, offset, field_type);
+        let new = match field.operation {
+            Operation::Get => {
+                replies.push(RESP::Integer(old));
+                continue;
+            }
+            // Unsigned fields take the value's two's complement bits, so
+            // negative values overflow upwards, as in Redis.
+            Operation::Set(value) if field_type.signed => {
+                field_type.fit(value as i128, field.overflow)
+            }
+            Operation::Set(value) => field_type.fit(value as u64 as i128, field.overflow),
+            Operation::IncrBy(increment) => {
+                field_type.fit(old as i128 + increment as i128, field.overflow)
+            }
+        };
+
+        let Some(new) = new else {
+            replies.push(RESP::NullBulkString);
+            continue;
+        };
+        write_field(buffer, offset, field_type.bits, new);
+        replies.push(RESP::Integer(match field.operation {
+            Operation::Set(_) => old,
+            _ => new,
+        }));
+    }
+    Ok(RESP::Array(replies))
+}
+
+/// Replies to a list of GET subcommands without creating the key.
+fn get_fields(ctx: &mut Context, key: &[u8], fields: &[Field]) -> CommandResult {
+    let bytes = lookup_bytes(ctx, key)?.unwrap_or_default();
+    Ok(RESP::Array(
+        fields
+            .iter()
+            .map(|field| RESP::Integer(read_field(&bytes, field.offset, field.field_type)))
+            .collect(),
+    ))
+}
+
+/// BITFIELD_RO key [GET type offset] ...
+fn bitfield_ro(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let fields = parse_fields(&args[2..])?;
+    if fields
+        .iter()
+        .any(|field| !matches!(field.operation, Operation::Get))
+    {
+        return Err(CommandError::Message(
+            "BITFIELD_RO only supports the GET subcommand",
+        ));
+    }
+    get_fields(ctx, &args[1], &fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::tests::call, db::Db};
+
+    fn signed(bits: u32) -> FieldType {
+        FieldType { signed: true, bits }
+    }
+
+    fn unsigned(bits: u32) -> FieldType {
+        FieldType {
+            signed: false,
+            bits,
+        }
+    }
+
+    #[test]
+    fn every_width_round_trips_at_unaligned_offsets() {
+        let types = (1..=64).map(signed).chain((1..=63).map(unsigned));
+        for field_type in types {
+            let (min, max) = (field_type.min() as i64, field_type.max() as i64);
+            for offset in [0, 3, 13, 61] {
+                for value in [min, min + 1, -1, 0, 1, max - 1, max] {
+                    let Some(value) = field_type.fit(value as i128, Overflow::Fail) else {
+                        continue;
+                    };
+                    // Surrounding bits are set, and have to stay set.
+                    let mut buffer = vec![0xff; 18];
+                    write_field(&mut buffer, offset, field_type.bits, value);
+                    assert_eq!(
+                        read_field(&buffer, offset, field_type),
+                        value,
+                        "{} bits at {offset}",
+                        field_type.bits
+                    );
+
+                    let mut cleared = buffer.clone();
+                    write_field(&mut cleared, offset, field_type.bits, 0);
+                    let untouched = (0..buffer.len() as u64 * 8)
+                        .filter(|&bit| !(offset..offset + field_type.bits as u64).contains(&bit));
+                    for bit in untouched {
+                        assert_eq!(
+                            (cleared[(bit >> 3) as usize] >> (7 - (bit & 7))) & 1,
+                            1,
+                            "bit {bit} beside {} bits at {offset}",
+                            field_type.bits
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reads_past_the_end_as_zero() {
+        assert_eq!(read_field(&[0xff], 4, unsigned(8)), 0xf0);
+        assert_eq!(read_field(&[0xff], 4, signed(8)), -16);
+        assert_eq!(read_field(&[], 0, signed(64)), 0);
+    }
+
+    #[test]
+    fn overflow_modes_at_the_signed_extremes() {
+        for bits in [1, 8, 63, 64] {
+            let field_type = signed(bits);
+            let (min, max) = (field_type.min(), field_type.max());
+            let (min64, max64) = (min as i64, max as i64);
+
+            assert_eq!(field_type.fit(max + 1, Overflow::Wrap), Some(min64));
+            assert_eq!(field_type.fit(min - 1, Overflow::Wrap), Some(max64));
+            if bits > 1 {
+                assert_eq!(field_type.fit(max + 3, Overflow::Wrap), Some(min64 + 2));
+            }
+            assert_eq!(field_type.fit(max + 1, Overflow::Sat), Some(max64));
+            assert_eq!(field_type.fit(min - 1, Overflow::Sat), Some(min64));
+            assert_eq!(field_type.fit(max + 1, Overflow::Fail), None);
+            assert_eq!(field_type.fit(min - 1, Overflow::Fail), None);
+            assert_eq!(field_type.fit(max, Overflow::Fail), Some(max64));
+            assert_eq!(field_type.fit(min, Overflow::Fail), Some(min64));
+        }
+
+        let field_type = unsigned(63);
+        let max = field_type.max();
+        assert_eq!(field_type.fit(max + 1, Overflow::Wrap), Some(0));
+        assert_eq!(field_type.fit(-1, Overflow::Wrap), Some(max as i64));
+        assert_eq!(field_type.fit(max + 1, Overflow::Sat), Some(max as i64));
+        assert_eq!(field_type.fit(-1, Overflow::Sat), Some(0));
+        assert_eq!(field_type.fit(-1, Overflow::Fail), None);
+    }
+
+    #[test]
+    fn i64_and_u63_fields_at_an_unaligned_offset() {
+        let mut db = Db::default();
+        assert_eq!(
+            call(
+                &mut db,
+                &[
+                    "BITFIELD",
+                    "k",
+                    "SET",
+                    "i64",
+                    "5",
+                    "-9223372036854775808",
+                    "GET",
+                    "i64",
+                    "5"
+                ]
+            ),
+            b"*2\r\n:0\r\n:-9223372036854775808\r\n"
+        );
+        assert_eq!(
+            call(&mut db, &["BITFIELD", "k", "INCRBY", "i64", "5", "-1"]),
+            b"*1\r\n:9223372036854775807\r\n"
+        );
+        assert_eq!(
+            call(
+                &mut db,
+                &["BITFIELD", "k", "OVERFLOW", "SAT", "INCRBY", "i64", "5", "1"]
+            ),
+            b"*1\r\n:9223372036854775807\r\n"
+        );
+        assert_eq!(
+            call(
+                &mut db,
+                &[
+                    "BITFIELD", "k", "OVERFLOW", "FAIL", "INCRBY", "i64", "5", "1", "GET", "i64",
+                    "5"
+                ]
+            ),
+            b"*2\r\n$-1\r\n:9223372036854775807\r\n"
+        );
+
+        assert_eq!(
+            call(
+                &mut db,
+                &[
+                    "BITFIELD",
+                    "u",
+                    "SET",
+                    "u63",
+                    "3",
+                    "9223372036854775807",
+                    "GET",
+                    "u63",
+                    "3"
+                ]
+            ),
+            b"*2\r\n:0\r\n:9223372036854775807\r\n"
+        );
+        assert_eq!(
+            call(&mut db, &["BITFIELD", "u", "INCRBY", "u63", "3", "1"]),
+            b"*1\r\n:0\r\n"
+        );
+        assert_eq!(
+            call(
+                &mut db,
+                &["BITFIELD", "u", "OVERFLOW", "SAT", "INCRBY", "u63", "3", "-1"]
+            ),
+            b"*1\r\n:0\r\n"
+        );
+        // The field starts 3 bits in, so the first byte keeps its top bits.
+        call(&mut db, &["SETBIT", "u", "0", "1"]);
+        assert_eq!(
+            call(
+                &mut db,
+                &["BITFIELD_RO", "u", "GET", "u63", "3", "GET", "u3", "0"]
+            ),
+            b"*2\r\n:0\r\n:4\r\n"
+        );
+    }
+}
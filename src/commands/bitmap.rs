@@ -0,0 +1,471 @@
+use std::borrow::Cow;
+
+use bytes::Bytes;
+
+use super::{parse_i64, Command, CommandError, CommandFlags, CommandResult, Context};
+use crate::{db::Value, resp::RESP, types::StringValue};
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("setbit", setbit, 4, CommandFlags::WRITE, 1, 1, 1),
+    Command::new(
+        "getbit",
+        getbit,
+        3,
+        CommandFlags::READONLY.union(CommandFlags::FAST),
+        1,
+        1,
+        1,
+    ),
+    Command::new("bitcount", bitcount, -2, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("bitpos", bitpos, -3, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("bitop", bitop, -4, CommandFlags::WRITE, 2, -1, 1),
+];
+
+/// Bitmaps are strings, so they share the 512MB string size limit.
+pub(super) const MAX_BIT_OFFSET: i64 = 512 * 1024 * 1024 * 8 - 1;
+
+/// Parses a bit offset, which has to address a bit inside the largest
+/// allowed string.
+pub(super) fn parse_bit_offset(arg: &[u8]) -> Result<u64, CommandError> {
+    match parse_i64(arg) {
+        Ok(offset) if (0..=MAX_BIT_OFFSET).contains(&offset) => Ok(offset as u64),
+        _ => Err(CommandError::Message(
+            "bit offset is not an integer or out of range",
+        )),
+    }
+}
+
+/// The bytes of the string at `key`, or `None` if the key is missing.
+pub(super) fn lookup_bytes<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<Cow<'a, [u8]>>, CommandError> {
+    match ctx.db.get(key) {
+        Some(Value::String(value)) => Ok(Some(value.as_bytes())),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// The bytes of the string at `key` for writing, creating the key if it is
+/// missing and padding the string with zero bytes to at least `len` bytes.
+pub(super) fn lookup_buffer_mut<'a>(
+    ctx: &'a mut Context,
+    key: &Bytes,
+    len: usize,
+) -> Result<&'a mut Vec<u8>, CommandError> {
+    if !ctx.db.contains(key) {
+        ctx.db.set(
+            key.clone(),
+            Value::String(StringValue::Buffer(Vec::new())),
+            false,
+        );
+    }
+    let Some(Value::String(value)) = ctx.db.get_mut(key) else {
+        return Err(CommandError::WrongType);
+    };
+
+    let buffer = value.make_mut();
+    if buffer.len() < len {
+        buffer.resize(len, 0);
+    }
+    Ok(buffer)
+}
+
+/// SETBIT key offset value. Bit 0 is the most significant bit of the first
+/// byte, and the string grows with zero bytes to reach `offset`.
+fn setbit(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let key = &args[1];
+    let offset = parse_bit_offset(&args[2])?;
+    let on = match args[3].as_ref() {
+        b"0" => false,
+        b"1" => true,
+        _ => {
+            return Err(CommandError::Message(
+                "bit is not an integer or out of range",
+            ))
+        }
+    };
+
+    let byte = (offset >> 3) as usize;
+    let mask = 1u8 << (7 - (offset & 7));
+
+    let buffer = lookup_buffer_mut(ctx, key, byte + 1)?;
+    let old = buffer[byte] & mask != 0;
+    if on {
+        buffer[byte] |= mask;
+    } else {
+        buffer[byte] &= !mask;
+    }
+    Ok(RESP::Integer(old as i64))
+}
+
+fn getbit(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let offset = parse_bit_offset(&args[2])?;
+    let byte = (offset >> 3) as usize;
+    let bit = match lookup_bytes(ctx, &args[1])? {
+        Some(bytes) => bytes.get(byte).map_or(0, |b| (b >> (7 - (offset & 7))) & 1),
+        None => 0,
+    };
+    Ok(RESP::Integer(bit as i64))
+}
+
+/// Clamps a `start`/`end` pair, where negative values count back from
+/// `length`, to an inclusive range inside `0..length`. `None` means the range
+/// is empty.
+fn resolve_range(start: i64, end: i64, length: i64) -> Option<(i64, i64)> {
+    if start < 0 && end < 0 && start > end {
+        return None;
+    }
+    let resolve = |index: i64| {
+        if index < 0 {
+            (length + index).max(0)
+        } else {
+            index
+        }
+    };
+    let (start, end) = (resolve(start), resolve(end).min(length - 1));
+    (start <= end && length > 0).then_some((start, end))
+}
+
+/// Parses the optional `start end [BYTE | BIT]` arguments of BITCOUNT and
+/// BITPOS. The returned flag is true for BIT ranges.
+fn parse_range_unit(arg: Option<&Bytes>) -> Result<bool, CommandError> {
+    match arg.map(|unit| unit.to_ascii_uppercase()).as_deref() {
+        None | Some(b"BYTE") => Ok(false),
+        Some(b"BIT") => Ok(true),
+        Some(_) => Err(CommandError::Syntax),
+    }
+}
+
+/// Counts set bits a word at a time, which the compiler turns into
+/// `popcnt` where available.
+fn popcount(bytes: &[u8]) -> u64 {
+    let mut words = bytes.chunks_exact(8);
+    let mut count: u64 = words
+        .by_ref()
+        .map(|word| u64::from_ne_bytes(word.try_into().unwrap_or_default()).count_ones() as u64)
+        .sum();
+    count += words
+        .remainder()
+        .iter()
+        .map(|byte| byte.count_ones() as u64)
+        .sum::<u64>();
+    count
+}
+
+/// BITCOUNT key [start end [BYTE | BIT]]
+fn bitcount(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = match args.len() {
+        2 => None,
+        4 | 5 => Some((
+            parse_i64(&args[2])?,
+            parse_i64(&args[3])?,
+            parse_range_unit(args.get(4))?,
+        )),
+        _ => return Err(CommandError::Syntax),
+    };
+    let Some(bytes) = lookup_bytes(ctx, &args[1])? else {
+        return Ok(RESP::Integer(0));
+    };
+
+    let Some((start, end, bit_unit)) = range else {
+        return Ok(RESP::Integer(popcount(&bytes) as i64));
+    };
+    let length = bytes.len() as i64 * if bit_unit { 8 } else { 1 };
+    let Some((start, end)) = resolve_range(start, end, length) else {
+        return Ok(RESP::Integer(0));
+    };
+    if !bit_unit {
+        return Ok(RESP::Integer(
+            popcount(&bytes[start as usize..=end as usize]) as i64,
+        ));
+    }
+
+    // Count whole bytes, then take away the bits of the first and last bytes
+    // that fall outside the range.
+    let (first, last) = ((start >> 3) as usize, (end >> 3) as usize);
+    let mut count = popcount(&bytes[first..=last]);
+    count -= (bytes[first] & !(0xffu8 >> (start & 7))).count_ones() as u64;
+    count -= (bytes[last] & (((1u16 << (7 - (end & 7))) - 1) as u8)).count_ones() as u64;
+    Ok(RESP::Integer(count as i64))
+}
+
+/// The index of the first byte that differs from `skip`, comparing a word at
+/// a time.
+fn find_byte_not_equal(bytes: &[u8], skip: u8) -> Option<usize> {
+    let skip_word = u64::from_ne_bytes([skip; 8]);
+    let mut words = bytes.chunks_exact(8);
+    let whole = words
+        .by_ref()
+        .position(|word| u64::from_ne_bytes(word.try_into().unwrap_or_default()) != skip_word)
+        .unwrap_or(bytes.len() / 8);
+    bytes[whole * 8..]
+        .iter()
+        .position(|&byte| byte != skip)
+        .map(|index| whole * 8 + index)
+}
+
+/// BITPOS key bit [start [end [BYTE | BIT]]]
+fn bitpos(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let bit = match args[2].as_ref() {
+        b"0" => false,
+        b"1" => true,
+        _ => return Err(CommandError::Message("The bit argument must be 1 or 0.")),
+    };
+    if args.len() > 6 {
+        return Err(CommandError::Syntax);
+    }
+    let start = args.get(3).map(|arg| parse_i64(arg)).transpose()?;
+    let end = args.get(4).map(|arg| parse_i64(arg)).transpose()?;
+    let end_given = end.is_some();
+    let bit_unit = parse_range_unit(args.get(5))?;
+
+    let Some(bytes) = lookup_bytes(ctx, &args[1])? else {
+        return Ok(RESP::Integer(if bit { -1 } else { 0 }));
+    };
+
+    let length = bytes.len() as i64 * if bit_unit { 8 } else { 1 };
+    let Some((start, end)) = resolve_range(start.unwrap_or(0), end.unwrap_or(-1), length) else {
+        return Ok(RESP::Integer(-1));
+    };
+
+    let (first, last, head_mask, tail_mask) = if bit_unit {
+        (
+            (start >> 3) as usize,
+            (end >> 3) as usize,
+            !(0xffu8 >> (start & 7)),
+            ((1u16 << (7 - (end & 7))) - 1) as u8,
+        )
+    } else {
+        (start as usize, end as usize, 0, 0)
+    };
+
+    // Bits of the first and last byte that fall outside a BIT range are
+    // forced to the value we are not looking for so they never match.
+    let force = |byte: u8, mask: u8| if bit { byte & !mask } else { byte | mask };
+    let skip = if bit { 0x00 } else { 0xff };
+    let mut head = force(bytes[first], head_mask);
+    let mut tail = force(bytes[last], tail_mask);
+    if first == last {
+        head = force(head, tail_mask);
+        tail = head;
+    }
+
+    let found = if head != skip {
+        Some((first, head))
+    } else if first == last {
+        None
+    } else {
+        match find_byte_not_equal(&bytes[first + 1..last], skip) {
+            Some(index) => Some((first + 1 + index, bytes[first + 1 + index])),
+            None if tail != skip => Some((last, tail)),
+            None => None,
+        }
+    };
+
+    let position = match found {
+        Some((index, byte)) => {
+            let byte = if bit { byte } else { !byte };
+            (index * 8) as i64 + byte.leading_zeros() as i64
+        }
+        // Looking for a clear bit in an all-ones string finds the first bit
+        // past its end, unless the caller asked for an explicit end.
+        None if !bit && !end_given => ((last + 1) * 8) as i64,
+        None => -1,
+    };
+    Ok(RESP::Integer(position))
+}
+
+/// BITOP AND | OR | XOR | NOT destkey key [key ...]
+fn bitop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let operation = args[1].to_ascii_uppercase();
+    // `None` stands for NOT, which has a single source.
+    let combine: Option<fn(u64, u64) -> u64> = match operation.as_slice() {
+        b"AND" => Some(|a, b| a & b),
+        b"OR" => Some(|a, b| a | b),
+        b"XOR" => Some(|a, b| a ^ b),
+        b"NOT" if args.len() == 4 => None,
+        b"NOT" => {
+            return Err(CommandError::Message(
+                "BITOP NOT must be called with a single source key.",
+            ))
+        }
+        _ => return Err(CommandError::Syntax),
+    };
+
+    let mut sources = Vec::with_capacity(args.len() - 3);
+    for key in &args[3..] {
+        sources.push(lookup_bytes(ctx, key)?.unwrap_or_default().into_owned());
+    }
+
+    // Shorter sources behave as if padded with zero bytes.
+    let length = sources.iter().map(Vec::len).max().unwrap_or(0);
+    let destination = &args[2];
+    if length == 0 {
+        ctx.db.remove(destination);
+        return Ok(RESP::Integer(0));
+    }
+
+    let word = |source: &[u8], offset: usize| {
+        let mut bytes = [0u8; 8];
+        if offset < source.len() {
+            let available = (source.len() - offset).min(8);
+            bytes[..available].copy_from_slice(&source[offset..offset + available]);
+        }
+        u64::from_ne_bytes(bytes)
+    };
+
+    let mut result = Vec::with_capacity(length + 8);
+    for offset in (0..length).step_by(8) {
+        let first = word(&sources[0], offset);
+        let value = match combine {
+            Some(combine) => sources[1..]
+                .iter()
+                .fold(first, |value, source| combine(value, word(source, offset))),
+            None => !first,
+        };
+        result.extend_from_slice(&value.to_ne_bytes());
+    }
+    result.truncate(length);
+
+    ctx.db.set(
+        destination.clone(),
+        Value::String(StringValue::Buffer(result)),
+        false,
+    );
+    Ok(RESP::Integer(length as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::tests::call, db::Db};
+
+    fn integer(n: i64) -> Vec<u8> {
+        format!(":{n}\r\n").into_bytes()
+    }
+
+    /// Stores `bits`, most significant bit first, at `key` with SETBIT.
+    fn set_bits(db: &mut Db, key: &str, bits: &[bool]) {
+        for (offset, &bit) in bits.iter().enumerate() {
+            let value = if bit { "1" } else { "0" };
+            call(db, &["SETBIT", key, &offset.to_string(), value]);
+        }
+    }
+
+    #[test]
+    fn resolves_negative_and_out_of_range_indexes() {
+        assert_eq!(resolve_range(0, -1, 10), Some((0, 9)));
+        assert_eq!(resolve_range(-3, -1, 10), Some((7, 9)));
+        assert_eq!(resolve_range(-100, 100, 10), Some((0, 9)));
+        assert_eq!(resolve_range(2, 2, 10), Some((2, 2)));
+        assert_eq!(resolve_range(9, -1, 10), Some((9, 9)));
+        assert_eq!(resolve_range(-1, -3, 10), None);
+        assert_eq!(resolve_range(5, 2, 10), None);
+        assert_eq!(resolve_range(10, 20, 10), None);
+        // Like Redis, an end before the start of the string still covers
+        // its first element.
+        assert_eq!(resolve_range(0, -11, 10), Some((0, 0)));
+        assert_eq!(resolve_range(0, -1, 0), None);
+    }
+
+    #[test]
+    fn popcount_matches_bytewise_count_across_word_boundaries() {
+        let bytes: Vec<u8> = (0..40u32).map(|i| (i * 37 + 11) as u8).collect();
+        for start in 0..9 {
+            for end in start..bytes.len() {
+                let slice = &bytes[start..end];
+                let expected: u64 = slice.iter().map(|b| b.count_ones() as u64).sum();
+                assert_eq!(popcount(slice), expected, "{start}..{end}");
+            }
+        }
+        assert_eq!(popcount(&[0xff; 17]), 136);
+    }
+
+    #[test]
+    fn bit_ranges_mask_the_first_and_last_byte() {
+        let bits: Vec<bool> = (0..29).map(|i| (i * 7 + i / 3) % 5 < 2).collect();
+        let mut db = Db::default();
+        set_bits(&mut db, "k", &bits);
+        let length = 32;
+        // Around the edges of each byte, and past both ends.
+        let indexes = [
+            -34, -33, -32, -31, -25, -9, -8, -7, -1, 0, 1, 6, 7, 8, 9, 15, 16, 23, 24, 28, 29, 31,
+            32, 33,
+        ];
+
+        for start in indexes {
+            for end in indexes {
+                let range = resolve_range(start, end, length);
+                let in_range =
+                    |i: &usize| range.is_some_and(|(s, e)| (s..=e).contains(&(*i as i64)));
+                let (start, end) = (start.to_string(), end.to_string());
+
+                let count = (0..bits.len())
+                    .filter(in_range)
+                    .filter(|&i| bits[i])
+                    .count();
+                assert_eq!(
+                    call(&mut db, &["BITCOUNT", "k", &start, &end, "BIT"]),
+                    integer(count as i64),
+                    "BITCOUNT {start} {end}"
+                );
+
+                for (bit, wanted) in [("1", true), ("0", false)] {
+                    // Bits past the 29 that were set are padding zeros.
+                    let position = (0..length as usize)
+                        .filter(in_range)
+                        .find(|&i| bits.get(i).copied().unwrap_or(false) == wanted);
+                    assert_eq!(
+                        call(&mut db, &["BITPOS", "k", bit, &start, &end, "BIT"]),
+                        integer(position.map_or(-1, |i| i as i64)),
+                        "BITPOS {bit} {start} {end}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn byte_ranges_count_whole_bytes() {
+        let mut db = Db::default();
+        call(&mut db, &["SET", "k", "foobar"]);
+        assert_eq!(call(&mut db, &["BITCOUNT", "k"]), integer(26));
+        assert_eq!(call(&mut db, &["BITCOUNT", "k", "0", "0"]), integer(4));
+        assert_eq!(call(&mut db, &["BITCOUNT", "k", "1", "1"]), integer(6));
+        assert_eq!(
+            call(&mut db, &["BITCOUNT", "k", "-2", "-1", "BYTE"]),
+            integer(7)
+        );
+        assert_eq!(
+            call(&mut db, &["BITCOUNT", "k", "5", "30", "BIT"]),
+            integer(17)
+        );
+        assert_eq!(call(&mut db, &["BITCOUNT", "k", "4", "2"]), integer(0));
+        assert_eq!(
+            call(&mut db, &["BITCOUNT", "missing", "0", "-1"]),
+            integer(0)
+        );
+    }
+
+    #[test]
+    fn bitpos_finds_a_clear_bit_past_the_end_unless_the_end_is_given() {
+        let mut db = Db::default();
+        set_bits(&mut db, "k", &[true; 16]);
+        assert_eq!(call(&mut db, &["BITPOS", "k", "0"]), integer(16));
+        assert_eq!(call(&mut db, &["BITPOS", "k", "0", "1"]), integer(16));
+        assert_eq!(call(&mut db, &["BITPOS", "k", "0", "0", "-1"]), integer(-1));
+        assert_eq!(
+            call(&mut db, &["BITPOS", "k", "0", "0", "15", "BIT"]),
+            integer(-1)
+        );
+        assert_eq!(call(&mut db, &["BITPOS", "k", "1", "1"]), integer(8));
+        assert_eq!(call(&mut db, &["BITPOS", "missing", "0"]), integer(0));
+        assert_eq!(call(&mut db, &["BITPOS", "missing", "1"]), integer(-1));
+
+        set_bits(&mut db, "zeros", &[false; 24]);
+        assert_eq!(call(&mut db, &["BITPOS", "zeros", "1"]), integer(-1));
+        assert_eq!(call(&mut db, &["BITPOS", "zeros", "0", "2"]), integer(16));
+    }
+}
@@ -0,0 +1,63 @@
+use bytes::Bytes;
+
+use super::{Command, CommandError, CommandFlags, CommandResult, Context};
+use crate::resp::RESP;
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("ping", ping, -1, CommandFlags::FAST, 0, 0, 0),
+    Command::new("echo", echo, 2, CommandFlags::FAST, 0, 0, 0),
+    Command::new("command", command, -1, CommandFlags::NONE, 0, 0, 0),
+];
+
+fn ping(_ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    match args {
+        [_] => Ok(RESP::SimpleString("PONG".to_string())),
+        [_, message] => Ok(RESP::BulkString(message.clone())),
+        _ => Err(CommandError::WrongArity("ping".to_string())),
+    }
+}
+
+fn echo(_ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    Ok(RESP::BulkString(args[1].clone()))
+}
+
+fn command(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(subcommand) = args.get(1) else {
+        return Ok(RESP::Array(ctx.commands.iter().map(command_info).collect()));
+    };
+
+    match subcommand.to_ascii_uppercase().as_slice() {
+        b"COUNT" if args.len() == 2 => Ok(RESP::Integer(ctx.commands.len() as i64)),
+        b"INFO" => Ok(RESP::Array(
+            args[2..]
+                .iter()
+                .map(|name| match ctx.commands.lookup(name) {
+                    Some(command) => command_info(command),
+                    None => RESP::NullArray,
+                })
+                .collect(),
+        )),
+        b"DOCS" => Ok(RESP::Array(Vec::new())),
+        _ => Err(CommandError::UnknownSubcommand(
+            String::from_utf8_lossy(subcommand).into_owned(),
+            "COMMAND".to_string(),
+        )),
+    }
+}
+
+fn command_info(command: &Command) -> RESP {
+    RESP::Array(vec![
+        RESP::BulkString(Bytes::from_static(command.name.as_bytes())),
+        RESP::Integer(command.arity as i64),
+        RESP::Array(
+            command
+                .flags
+                .names()
+                .map(|name| RESP::SimpleString(name.to_string()))
+                .collect(),
+        ),
+        RESP::Integer(command.first_key as i64),
+        RESP::Integer(command.last_key as i64),
+        RESP::Integer(command.step as i64),
+    ])
+}
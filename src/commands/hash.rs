@@ -0,0 +1,710 @@
+use bytes::Bytes;
+
+use super::{
+    format_f64, parse_f64, parse_i64, random_sample, string::parse_expire_option, Command,
+    CommandError, CommandFlags, CommandResult, Context, ScanArgs,
+};
+use crate::{
+    db::{now_ms, Value},
+    resp::RESP,
+    types::{string_to_i64, Hash},
+};
+
+/// The latest expiry time a field can be given, in unix milliseconds, as in
+/// Redis.
+const MAX_FIELD_EXPIRY_MS: i64 = (1 << 46) - 1;
+
+const READONLY_FAST: CommandFlags = CommandFlags::READONLY.union(CommandFlags::FAST);
+const WRITE_FAST: CommandFlags = CommandFlags::WRITE.union(CommandFlags::FAST);
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("hset", hset, -4, WRITE_FAST, 1, 1, 1),
+    Command::new("hsetnx", hsetnx, 4, WRITE_FAST, 1, 1, 1),
+    Command::new("hget", hget, 3, READONLY_FAST, 1, 1, 1),
+    Command::new("hmget", hmget, -3, READONLY_FAST, 1, 1, 1),
+    Command::new("hgetall", hgetall, 2, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("hdel", hdel, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("hexists", hexists, 3, READONLY_FAST, 1, 1, 1),
+    Command::new("hlen", hlen, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("hkeys", hkeys, 2, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("hvals", hvals, 2, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("hstrlen", hstrlen, 3, READONLY_FAST, 1, 1, 1),
+    Command::new("hincrby", hincrby, 4, WRITE_FAST, 1, 1, 1),
+    Command::new("hincrbyfloat", hincrbyfloat, 4, WRITE_FAST, 1, 1, 1),
+    Command::new(
+        "hrandfield",
+        hrandfield,
+        -2,
+        CommandFlags::READONLY,
+        1,
+        1,
+        1,
+    ),
+    Command::new("hscan", hscan, -3, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("hexpire", hexpire, -6, WRITE_FAST, 1, 1, 1),
+    Command::new("hpexpire", hpexpire, -6, WRITE_FAST, 1, 1, 1),
+    Command::new("hexpireat", hexpireat, -6, WRITE_FAST, 1, 1, 1),
+    Command::new("hpexpireat", hpexpireat, -6, WRITE_FAST, 1, 1, 1),
+    Command::new("httl", httl, -5, READONLY_FAST, 1, 1, 1),
+    Command::new("hpttl", hpttl, -5, READONLY_FAST, 1, 1, 1),
+    Command::new("hexpiretime", hexpiretime, -5, READONLY_FAST, 1, 1, 1),
+    Command::new("hpexpiretime", hpexpiretime, -5, READONLY_FAST, 1, 1, 1),
+    Command::new("hpersist", hpersist, -5, WRITE_FAST, 1, 1, 1),
+    Command::new("hgetex", hgetex, -5, WRITE_FAST, 1, 1, 1),
+    Command::new("hsetex", hsetex, -6, WRITE_FAST, 1, 1, 1),
+];
+
+/// The hash stored at `key`, if there is one.
+fn lookup_hash<'a>(ctx: &'a mut Context, key: &[u8]) -> Result<Option<&'a Hash>, CommandError> {
+    match ctx.db.get(key) {
+        Some(Value::Hash(hash)) => Ok(Some(hash)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn lookup_hash_mut<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<&'a mut Hash>, CommandError> {
+    match ctx.db.get_mut(key) {
+        Some(Value::Hash(hash)) => Ok(Some(hash)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// The hash stored at `key`, created empty if the key is missing.
+fn lookup_hash_or_create<'a>(
+    ctx: &'a mut Context,
+    key: &Bytes,
+) -> Result<&'a mut Hash, CommandError> {
+    if !ctx.db.contains(key) {
+        let hash = Hash::new(
+            ctx.config.hash_max_listpack_entries,
+            ctx.config.hash_max_listpack_value,
+        );
+        ctx.db.set(key.clone(), Value::Hash(hash), false);
+    }
+    let Some(hash) = lookup_hash_mut(ctx, key)? else {
+        unreachable!("key was just created");
+    };
+    Ok(hash)
+}
+
+/// Deletes `key` if it holds a hash that has been emptied.
+fn remove_if_empty(ctx: &mut Context, key: &[u8]) {
+    if matches!(ctx.db.get(key), Some(Value::Hash(hash)) if hash.is_empty()) {
+        ctx.db.remove(key);
+    }
+}
+
+fn bulk(value: &[u8]) -> RESP {
+    RESP::BulkString(Bytes::copy_from_slice(value))
+}
+
+/// HSET key field value [field value ...], replying with how many fields
+/// were added rather than updated.
+fn hset(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    if !args[2..].chunks_exact(2).remainder().is_empty() {
+        return Err(CommandError::WrongArity("hset".to_string()));
+    }
+    let hash = lookup_hash_or_create(ctx, &args[1])?;
+    let added = args[2..]
+        .chunks_exact(2)
+        .filter(|pair| hash.insert(&pair[0], &pair[1], false))
+        .count();
+    Ok(RESP::Integer(added as i64))
+}
+
+fn hsetnx(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    if lookup_hash(ctx, &args[1])?.is_some_and(|hash| hash.contains(&args[2])) {
+        return Ok(RESP::Integer(0));
+    }
+    lookup_hash_or_create(ctx, &args[1])?.insert(&args[2], &args[3], false);
+    Ok(RESP::Integer(1))
+}
+
+fn hget(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let value = lookup_hash(ctx, &args[1])?.and_then(|hash| hash.get(&args[2]));
+    Ok(value.map_or(RESP::NullBulkString, bulk))
+}
+
+fn hmget(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let hash = lookup_hash(ctx, &args[1])?;
+    let values = args[2..]
+        .iter()
+        .map(|field| {
+            hash.and_then(|hash| hash.get(field))
+                .map_or(RESP::NullBulkString, bulk)
+        })
+        .collect();
+    Ok(RESP::Array(values))
+}
+
+fn hgetall(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(hash) = lookup_hash(ctx, &args[1])? else {
+        return Ok(RESP::Array(vec![]));
+    };
+    let entries = hash
+        .iter()
+        .flat_map(|(field, value)| [bulk(field), bulk(value)])
+        .collect();
+    Ok(RESP::Array(entries))
+}
+
+fn hdel(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(hash) = lookup_hash_mut(ctx, &args[1])? else {
+        return Ok(RESP::Integer(0));
+    };
+    let removed = args[2..].iter().filter(|field| hash.remove(field)).count();
+    remove_if_empty(ctx, &args[1]);
+    Ok(RESP::Integer(removed as i64))
+}
+
+fn hexists(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let exists = lookup_hash(ctx, &args[1])?.is_some_and(|hash| hash.contains(&args[2]));
+    Ok(RESP::Integer(exists as i64))
+}
+
+fn hlen(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let len = lookup_hash(ctx, &args[1])?.map_or(0, Hash::len);
+    Ok(RESP::Integer(len as i64))
+}
+
+fn hkeys(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(hash) = lookup_hash(ctx, &args[1])? else {
+        return Ok(RESP::Array(vec![]));
+    };
+    Ok(RESP::Array(
+        hash.iter().map(|(field, _)| bulk(field)).collect(),
+    ))
+}
+
+fn hvals(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(hash) = lookup_hash(ctx, &args[1])? else {
+        return Ok(RESP::Array(vec![]));
+    };
+    Ok(RESP::Array(
+        hash.iter().map(|(_, value)| bulk(value)).collect(),
+    ))
+}
+
+fn hstrlen(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let len = lookup_hash(ctx, &args[1])?
+        .and_then(|hash| hash.get(&args[2]))
+        .map_or(0, <[u8]>::len);
+    Ok(RESP::Integer(len as i64))
+}
+
+fn hincrby(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let increment = parse_i64(&args[3])?;
+    let hash = lookup_hash_or_create(ctx, &args[1])?;
+    let current = match hash.get(&args[2]) {
+        Some(value) => {
+            string_to_i64(value).ok_or(CommandError::Message("hash value is not an integer"))?
+        }
+        None => 0,
+    };
+    let value = current.checked_add(increment).ok_or(CommandError::Message(
+        "increment or decrement would overflow",
+    ))?;
+    hash.insert(&args[2], value.to_string().as_bytes(), true);
+    Ok(RESP::Integer(value))
+}
+
+fn hincrbyfloat(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let increment = parse_f64(&args[3])?;
+    if !increment.is_finite() {
+        return Err(CommandError::Message(
+            "increment would produce NaN or Infinity",
+        ));
+    }
+    let hash = lookup_hash_or_create(ctx, &args[1])?;
+    let current = match hash.get(&args[2]) {
+        Some(value) => {
+            parse_f64(value).map_err(|_| CommandError::Message("hash value is not a float"))?
+        }
+        None => 0.0,
+    };
+    let value = current + increment;
+    if !value.is_finite() {
+        return Err(CommandError::Message(
+            "increment would produce NaN or Infinity",
+        ));
+    }
+    let value = format_f64(value);
+    hash.insert(&args[2], value.as_bytes(), true);
+    Ok(RESP::BulkString(Bytes::from(value)))
+}
+
+/// HRANDFIELD key [count [WITHVALUES]], sampled by `random_sample`.
+fn hrandfield(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let (count, with_values) = match args {
+        [_, _] => (None, false),
+        [_, _, count] => (Some(parse_i64(count)?), false),
+        [_, _, count, option] if option.eq_ignore_ascii_case(b"WITHVALUES") => {
+            (Some(parse_i64(count)?), true)
+        }
+        _ => return Err(CommandError::Syntax),
+    };
+    if count.is_some_and(|count| count.unsigned_abs() > i64::MAX as u64 / 2) {
+        return Err(CommandError::Message("value is out of range"));
+    }
+
+    let hash = lookup_hash(ctx, &args[1])?;
+    let Some(count) = count else {
+        let field = hash.and_then(Hash::random_entry).map(|(field, _)| field);
+        return Ok(field.map_or(RESP::NullBulkString, bulk));
+    };
+    let Some(hash) = hash else {
+        return Ok(RESP::Array(vec![]));
+    };
+
+    let entries = random_sample(
+        count,
+        hash.len(),
+        hash.iter(),
+        || hash.random_entry(),
+        |&(field, _)| field,
+    );
+
+    let reply = entries
+        .into_iter()
+        .flat_map(|(field, value)| {
+            let value = with_values.then(|| bulk(value));
+            std::iter::once(bulk(field)).chain(value)
+        })
+        .collect();
+    Ok(RESP::Array(reply))
+}
+
+/// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+fn hscan(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let scan = ScanArgs::parse(&args[2..], true)?;
+    let mut items = Vec::new();
+    let cursor = match lookup_hash(ctx, &args[1])? {
+        Some(hash) => hash.scan(scan.cursor, scan.count, |field, value| {
+            if scan.matches(field) {
+                items.push(bulk(field));
+                if !scan.novalues {
+                    items.push(bulk(value));
+                }
+            }
+        }),
+        None => 0,
+    };
+    Ok(RESP::Array(vec![
+        RESP::BulkString(Bytes::from(cursor.to_string())),
+        RESP::Array(items),
+    ]))
+}
+
+/// Parses `FIELDS numfields field [field ...]`, where each field comes with
+/// `arity - 1` more arguments, returning the arguments after `numfields`.
+fn parse_fields(args: &[Bytes], arity: usize) -> Result<&[Bytes], CommandError> {
+    if !args[0].eq_ignore_ascii_case(b"FIELDS") {
+        return Err(CommandError::Message(
+            "Mandatory argument FIELDS is missing or not at the right position",
+        ));
+    }
+    let count = args
+        .get(1)
+        .and_then(|count| string_to_i64(count))
+        .filter(|&count| count > 0)
+        .ok_or(CommandError::Message(
+            "Parameter `numFields` should be greater than 0",
+        ))?;
+    let fields = &args[2..];
+    if fields.len() as u64 != count as u64 * arity as u64 {
+        return Err(CommandError::Message(
+            "The `numfields` parameter must match the number of arguments",
+        ));
+    }
+    Ok(fields)
+}
+
+fn hexpire(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    hexpire_generic(ctx, args, "hexpire", 1000, false)
+}
+
+fn hpexpire(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    hexpire_generic(ctx, args, "hpexpire", 1, false)
+}
+
+fn hexpireat(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    hexpire_generic(ctx, args, "hexpireat", 1000, true)
+}
+
+fn hpexpireat(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    hexpire_generic(ctx, args, "hpexpireat", 1, true)
+}
+
+/// HEXPIRE key time [NX | XX | GT | LT] FIELDS numfields field [field ...],
+/// with `time` in units of `unit_ms` milliseconds, relative to now unless
+/// `absolute` is set. Replies per field with -2 if it doesn't exist, 0 if
+/// the condition wasn't met, 1 if the expiry was set and 2 if the field was
+/// deleted because the time has already passed.
+fn hexpire_generic(
+    ctx: &mut Context,
+    args: &[Bytes],
+    name: &'static str,
+    unit_ms: i64,
+    absolute: bool,
+) -> CommandResult {
+    let time = parse_i64(&args[2])?;
+    if time < 0 {
+        return Err(CommandError::Message("invalid expire time, must be >= 0"));
+    }
+    let base = if absolute { 0 } else { now_ms() as i64 };
+    let when = time
+        .checked_mul(unit_ms)
+        .and_then(|millis| millis.checked_add(base))
+        .filter(|&when| when <= MAX_FIELD_EXPIRY_MS)
+        .ok_or(CommandError::InvalidExpireTime(name))? as u64;
+
+    let condition = args[3].to_ascii_uppercase();
+    let (condition, fields) = match condition.as_slice() {
+        b"NX" | b"XX" | b"GT" | b"LT" => (Some(condition.as_slice()), parse_fields(&args[4..], 1)?),
+        _ => (None, parse_fields(&args[3..], 1)?),
+    };
+
+    let key = &args[1];
+    let Some(hash) = lookup_hash_mut(ctx, key)? else {
+        return Ok(RESP::Array(
+            fields.iter().map(|_| RESP::Integer(-2)).collect(),
+        ));
+    };
+    let now = now_ms();
+    let replies = fields
+        .iter()
+        .map(|field| {
+            if !hash.contains(field) {
+                return RESP::Integer(-2);
+            }
+            // A field without an expiry counts as never expiring.
+            let allowed = match (hash.field_expiry(field), condition) {
+                (Some(_), Some(b"NX")) => false,
+                (None, Some(b"XX" | b"GT")) => false,
+                (Some(current), Some(b"GT")) => when > current,
+                (Some(current), Some(b"LT")) => when < current,
+                _ => true,
+            };
+            if !allowed {
+                return RESP::Integer(0);
+            }
+            if when <= now {
+                hash.remove(field);
+                return RESP::Integer(2);
+            }
+            hash.set_field_expiry(field, when);
+            RESP::Integer(1)
+        })
+        .collect();
+    remove_if_empty(ctx, key);
+    ctx.db.track_field_expiries(key);
+    Ok(RESP::Array(replies))
+}
+
+fn httl(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    httl_generic(ctx, args, |when, now| {
+        when.saturating_sub(now).div_ceil(1000)
+    })
+}
+
+fn hpttl(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    httl_generic(ctx, args, |when, now| when.saturating_sub(now))
+}
+
+fn hexpiretime(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    httl_generic(ctx, args, |when, _| when / 1000)
+}
+
+fn hpexpiretime(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    httl_generic(ctx, args, |when, _| when)
+}
+
+/// HTTL key FIELDS numfields field [field ...]. Replies per field with -2 if
+/// it doesn't exist, -1 if it has no expiry, and otherwise its expiry time
+/// turned by `convert` into the reply from the time and the current time.
+fn httl_generic(ctx: &mut Context, args: &[Bytes], convert: fn(u64, u64) -> u64) -> CommandResult {
+    let fields = parse_fields(&args[2..], 1)?;
+    let Some(hash) = lookup_hash(ctx, &args[1])? else {
+        return Ok(RESP::Array(
+            fields.iter().map(|_| RESP::Integer(-2)).collect(),
+        ));
+    };
+    let now = now_ms();
+    let replies = fields
+        .iter()
+        .map(|field| match hash.field_expiry(field) {
+            _ if !hash.contains(field) => RESP::Integer(-2),
+            Some(when) => RESP::Integer(convert(when, now) as i64),
+            None => RESP::Integer(-1),
+        })
+        .collect();
+    Ok(RESP::Array(replies))
+}
+
+/// HPERSIST key FIELDS numfields field [field ...]. Replies per field with
+/// -2 if it doesn't exist, -1 if it had no expiry and 1 if it was removed.
+fn hpersist(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let fields = parse_fields(&args[2..], 1)?;
+    let Some(hash) = lookup_hash_mut(ctx, &args[1])? else {
+        return Ok(RESP::Array(
+            fields.iter().map(|_| RESP::Integer(-2)).collect(),
+        ));
+    };
+    let replies = fields
+        .iter()
+        .map(|field| match hash.contains(field) {
+            false => RESP::Integer(-2),
+            true if hash.persist_field(field) => RESP::Integer(1),
+            true => RESP::Integer(-1),
+        })
+        .collect();
+    Ok(RESP::Array(replies))
+}
+
+/// HGETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+/// PXAT unix-time-milliseconds | PERSIST] FIELDS numfields field [field ...]
+fn hgetex(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let mut expire_at = None;
+    let mut persist = false;
+
+    let mut i = 2;
+    while !args[i].eq_ignore_ascii_case(b"FIELDS") {
+        let option = args[i].to_ascii_uppercase();
+        match option.as_slice() {
+            b"PERSIST" if expire_at.is_none() && !persist => persist = true,
+            b"EX" | b"PX" | b"EXAT" | b"PXAT" if expire_at.is_none() && !persist => {
+                i += 1;
+                expire_at = Some(parse_expire_option(&option, args.get(i), "hgetex")?);
+            }
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 1;
+        if i == args.len() {
+            return Err(CommandError::Syntax);
+        }
+    }
+    let fields = parse_fields(&args[i..], 1)?;
+
+    let key = &args[1];
+    let Some(hash) = lookup_hash_mut(ctx, key)? else {
+        return Ok(RESP::Array(
+            fields.iter().map(|_| RESP::NullBulkString).collect(),
+        ));
+    };
+    let now = now_ms();
+    let mut replies = Vec::with_capacity(fields.len());
+    for field in fields {
+        let Some(value) = hash.get(field) else {
+            replies.push(RESP::NullBulkString);
+            continue;
+        };
+        replies.push(bulk(value));
+        match expire_at {
+            Some(when) if when <= now => {
+                hash.remove(field);
+            }
+            Some(when) => hash.set_field_expiry(field, when),
+            None if persist => {
+                hash.persist_field(field);
+            }
+            None => {}
+        }
+    }
+    remove_if_empty(ctx, key);
+    ctx.db.track_field_expiries(key);
+    Ok(RESP::Array(replies))
+}
+
+/// HSETEX key [FNX | FXX] [EX seconds | PX milliseconds |
+/// EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+/// FIELDS numfields field value [field value ...]
+///
+/// FNX only sets the fields if none of them exist and FXX only if all of
+/// them do, replying 1 if they were set and 0 otherwise.
+fn hsetex(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let mut condition = None;
+    let mut expire_at = None;
+    let mut keep_ttl = false;
+
+    let mut i = 2;
+    while !args[i].eq_ignore_ascii_case(b"FIELDS") {
+        let option = args[i].to_ascii_uppercase();
+        match option.as_slice() {
+            b"FNX" | b"FXX" if condition.is_none() => condition = Some(option),
+            b"KEEPTTL" if expire_at.is_none() && !keep_ttl => keep_ttl = true,
+            b"EX" | b"PX" | b"EXAT" | b"PXAT" if expire_at.is_none() && !keep_ttl => {
+                i += 1;
+                expire_at = Some(parse_expire_option(&option, args.get(i), "hsetex")?);
+            }
+            _ => return Err(CommandError::Syntax),
+        }
+        i += 1;
+        if i == args.len() {
+            return Err(CommandError::Syntax);
+        }
+    }
+    let pairs = parse_fields(&args[i..], 2)?;
+
+    let key = &args[1];
+    if let Some(condition) = condition {
+        let hash = lookup_hash(ctx, key)?;
+        let mut fields = pairs.iter().step_by(2);
+        let met = match condition.as_slice() {
+            b"FNX" => !fields.any(|field| hash.is_some_and(|hash| hash.contains(field))),
+            _ => fields.all(|field| hash.is_some_and(|hash| hash.contains(field))),
+        };
+        if !met {
+            return Ok(RESP::Integer(0));
+        }
+    }
+
+    let hash = lookup_hash_or_create(ctx, key)?;
+    let now = now_ms();
+    for pair in pairs.chunks_exact(2) {
+        hash.insert(&pair[0], &pair[1], keep_ttl);
+        match expire_at {
+            Some(when) if when <= now => {
+                hash.remove(&pair[0]);
+            }
+            Some(when) => hash.set_field_expiry(&pair[0], when),
+            None => {}
+        }
+    }
+    remove_if_empty(ctx, key);
+    ctx.db.track_field_expiries(key);
+    Ok(RESP::Integer(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::tests::call, db::Db};
+
+    /// A hash at `key` holding fields `a`, `b` and `c`.
+    fn db_with_hash(key: &str) -> Db {
+        let mut db = Db::default();
+        call(&mut db, &["HSET", key, "a", "1", "b", "2", "c", "3"]);
+        db
+    }
+
+    #[test]
+    fn conditions_compare_against_the_current_expiry() {
+        let mut db = db_with_hash("h");
+        let at = (now_ms() + 100_000).to_string();
+        let later = (now_ms() + 200_000).to_string();
+        let earlier = (now_ms() + 50_000).to_string();
+        let pxat = |db: &mut Db, time: &str, condition: &[&str], field: &str| {
+            let mut args = vec!["HPEXPIREAT", "h", time];
+            args.extend_from_slice(condition);
+            args.extend_from_slice(&["FIELDS", "1", field]);
+            call(db, &args)
+        };
+        let one = b"*1\r\n:1\r\n".to_vec();
+        let zero = b"*1\r\n:0\r\n".to_vec();
+
+        // Without an expiry, NX and LT apply while XX and GT do not.
+        assert_eq!(pxat(&mut db, &at, &["XX"], "a"), zero);
+        assert_eq!(pxat(&mut db, &at, &["GT"], "a"), zero);
+        assert_eq!(pxat(&mut db, &at, &["NX"], "a"), one);
+        assert_eq!(pxat(&mut db, &at, &["LT"], "b"), one);
+
+        assert_eq!(pxat(&mut db, &later, &["NX"], "a"), zero);
+        assert_eq!(pxat(&mut db, &earlier, &["GT"], "a"), zero);
+        assert_eq!(pxat(&mut db, &later, &["GT"], "a"), one);
+        assert_eq!(pxat(&mut db, &later, &["LT"], "a"), zero);
+        assert_eq!(pxat(&mut db, &earlier, &["LT"], "a"), one);
+        assert_eq!(pxat(&mut db, &later, &["XX"], "a"), one);
+        assert_eq!(
+            call(&mut db, &["HPEXPIRETIME", "h", "FIELDS", "2", "a", "c"]),
+            format!("*2\r\n:{later}\r\n:-1\r\n").into_bytes()
+        );
+    }
+
+    #[test]
+    fn missing_fields_and_keys_reply_minus_two() {
+        let mut db = db_with_hash("h");
+        assert_eq!(
+            call(
+                &mut db,
+                &["HEXPIRE", "h", "100", "FIELDS", "2", "a", "nope"]
+            ),
+            b"*2\r\n:1\r\n:-2\r\n"
+        );
+        assert_eq!(
+            call(
+                &mut db,
+                &["HEXPIRE", "missing", "100", "FIELDS", "2", "a", "b"]
+            ),
+            b"*2\r\n:-2\r\n:-2\r\n"
+        );
+    }
+
+    #[test]
+    fn a_time_in_the_past_deletes_the_field() {
+        let mut db = db_with_hash("h");
+        call(&mut db, &["HEXPIRE", "h", "100", "FIELDS", "1", "a"]);
+        assert_eq!(
+            call(&mut db, &["HPEXPIREAT", "h", "1", "FIELDS", "2", "a", "b"]),
+            b"*2\r\n:2\r\n:2\r\n"
+        );
+        assert_eq!(call(&mut db, &["HKEYS", "h"]), b"*1\r\n$1\r\nc\r\n");
+        assert_eq!(
+            call(&mut db, &["HTTL", "h", "FIELDS", "1", "a"]),
+            b"*1\r\n:-2\r\n"
+        );
+
+        assert_eq!(
+            call(&mut db, &["HEXPIRE", "h", "0", "FIELDS", "1", "c"]),
+            b"*1\r\n:2\r\n"
+        );
+        assert_eq!(call(&mut db, &["EXISTS", "h"]), b":0\r\n");
+    }
+
+    #[test]
+    fn the_key_goes_once_its_last_field_expires() {
+        let mut db = db_with_hash("h");
+        call(
+            &mut db,
+            &["HEXPIRE", "h", "100", "FIELDS", "3", "a", "b", "c"],
+        );
+        let Some(Value::Hash(hash)) = db.get_mut(b"h") else {
+            panic!("h holds a hash");
+        };
+        for field in [&b"a"[..], b"b", b"c"] {
+            hash.set_field_expiry(field, 1);
+        }
+        assert_eq!(call(&mut db, &["EXISTS", "h"]), b":0\r\n");
+    }
+
+    #[test]
+    fn rejects_times_past_the_maximum() {
+        let mut db = db_with_hash("h");
+        let max = MAX_FIELD_EXPIRY_MS.to_string();
+        let past_max = (MAX_FIELD_EXPIRY_MS + 1).to_string();
+        assert_eq!(
+            call(&mut db, &["HPEXPIREAT", "h", &max, "FIELDS", "1", "a"]),
+            b"*1\r\n:1\r\n"
+        );
+        assert_eq!(
+            call(&mut db, &["HPEXPIREAT", "h", &past_max, "FIELDS", "1", "a"]),
+            b"-ERR invalid expire time in 'hpexpireat' command\r\n"
+        );
+        let huge = (i64::MAX / 1000).to_string();
+        assert_eq!(
+            call(&mut db, &["HEXPIRE", "h", &huge, "FIELDS", "1", "a"]),
+            b"-ERR invalid expire time in 'hexpire' command\r\n"
+        );
+        assert_eq!(
+            call(
+                &mut db,
+                &["HEXPIRE", "h", &i64::MAX.to_string(), "FIELDS", "1", "a"]
+            ),
+            b"-ERR invalid expire time in 'hexpire' command\r\n"
+        );
+    }
+}
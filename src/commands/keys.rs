@@ -0,0 +1,155 @@
+use bytes::Bytes;
+
+use super::{parse_i64, Command, CommandError, CommandFlags, CommandResult, Context};
+use crate::{db::now_ms, resp::RESP};
+
+const READONLY_FAST: CommandFlags = CommandFlags::READONLY.union(CommandFlags::FAST);
+const WRITE_FAST: CommandFlags = CommandFlags::WRITE.union(CommandFlags::FAST);
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("del", del, -2, CommandFlags::WRITE, 1, -1, 1),
+    Command::new("exists", exists, -2, READONLY_FAST, 1, -1, 1),
+    Command::new("expire", expire, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("pexpire", pexpire, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("expireat", expireat, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("pexpireat", pexpireat, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("ttl", ttl, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("pttl", pttl, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("expiretime", expiretime, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("pexpiretime", pexpiretime, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("persist", persist, 2, WRITE_FAST, 1, 1, 1),
+];
+
+fn del(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let removed = args[1..]
+        .iter()
+        .filter(|key| ctx.db.remove(key).is_some())
+        .count();
+    Ok(RESP::Integer(removed as i64))
+}
+
+fn exists(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    // Repeated keys are counted once per mention, as in Redis.
+    let found = args[1..].iter().filter(|key| ctx.db.contains(key)).count();
+    Ok(RESP::Integer(found as i64))
+}
+
+fn expire(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    expire_generic(ctx, args, "expire", 1000, false)
+}
+
+fn pexpire(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    expire_generic(ctx, args, "pexpire", 1, false)
+}
+
+fn expireat(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    expire_generic(ctx, args, "expireat", 1000, true)
+}
+
+fn pexpireat(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    expire_generic(ctx, args, "pexpireat", 1, true)
+}
+
+/// EXPIRE key time [NX | XX | GT | LT], with `time` in units of `unit_ms`
+/// milliseconds, relative to now unless `absolute` is set.
+fn expire_generic(
+    ctx: &mut Context,
+    args: &[Bytes],
+    name: &'static str,
+    unit_ms: i64,
+    absolute: bool,
+) -> CommandResult {
+    let (mut nx, mut xx, mut gt, mut lt) = (false, false, false, false);
+    for option in &args[3..] {
+        match option.to_ascii_uppercase().as_slice() {
+            b"NX" => nx = true,
+            b"XX" => xx = true,
+            b"GT" => gt = true,
+            b"LT" => lt = true,
+            _ => {
+                return Err(CommandError::UnsupportedOption(
+                    String::from_utf8_lossy(option).into_owned(),
+                ))
+            }
+        }
+    }
+    if nx && (xx || gt || lt) {
+        return Err(CommandError::Message(
+            "NX and XX, GT or LT options at the same time are not compatible",
+        ));
+    }
+    if gt && lt {
+        return Err(CommandError::Message(
+            "GT and LT options at the same time are not compatible",
+        ));
+    }
+
+    let base = if absolute { 0 } else { now_ms() as i64 };
+    let when = parse_i64(&args[2])?
+        .checked_mul(unit_ms)
+        .and_then(|millis| millis.checked_add(base))
+        .ok_or(CommandError::InvalidExpireTime(name))?;
+
+    let key = &args[1];
+    if !ctx.db.contains(key) {
+        return Ok(RESP::Integer(0));
+    }
+
+    let current = ctx.db.expiry(key).map(|current| current as i64);
+    let allowed = match current {
+        Some(_) if nx => false,
+        None if xx || gt => false,
+        Some(current) if gt => when > current,
+        Some(current) if lt => when < current,
+        _ => true,
+    };
+    if !allowed {
+        return Ok(RESP::Integer(0));
+    }
+
+    ctx.db.set_expiry(key, when.max(0) as u64);
+    Ok(RESP::Integer(1))
+}
+
+fn ttl(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    ttl_generic(ctx, &args[1], |remaining| (remaining + 500) / 1000)
+}
+
+fn pttl(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    ttl_generic(ctx, &args[1], |remaining| remaining)
+}
+
+fn expiretime(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    expiretime_generic(ctx, &args[1], 1000)
+}
+
+fn pexpiretime(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    expiretime_generic(ctx, &args[1], 1)
+}
+
+/// Replies -2 for a missing key, -1 for a key without an expiry, and
+/// otherwise the remaining time to live converted by `convert` from
+/// milliseconds.
+fn ttl_generic(ctx: &mut Context, key: &[u8], convert: fn(i64) -> i64) -> CommandResult {
+    if !ctx.db.contains(key) {
+        return Ok(RESP::Integer(-2));
+    }
+    Ok(RESP::Integer(match ctx.db.expiry(key) {
+        Some(when) => convert(when.saturating_sub(now_ms()) as i64),
+        None => -1,
+    }))
+}
+
+fn expiretime_generic(ctx: &mut Context, key: &[u8], unit_ms: u64) -> CommandResult {
+    if !ctx.db.contains(key) {
+        return Ok(RESP::Integer(-2));
+    }
+    Ok(RESP::Integer(match ctx.db.expiry(key) {
+        Some(when) => (when / unit_ms) as i64,
+        None => -1,
+    }))
+}
+
+fn persist(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    Ok(RESP::Integer(ctx.db.persist(&args[1]) as i64))
+}
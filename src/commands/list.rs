@@ -0,0 +1,530 @@
+use std::time::Instant;
+
+use bytes::Bytes;
+
+use super::{
+    parse_i64, parse_timeout, Block, Command, CommandError, CommandFlags, CommandResult, Context,
+};
+use crate::{db::Value, resp::RESP, types::List};
+
+const READONLY_FAST: CommandFlags = CommandFlags::READONLY.union(CommandFlags::FAST);
+const WRITE_FAST: CommandFlags = CommandFlags::WRITE.union(CommandFlags::FAST);
+const WRITE_BLOCKING: CommandFlags = CommandFlags::WRITE.union(CommandFlags::BLOCKING);
+const WRITE_MOVABLE_KEYS: CommandFlags = CommandFlags::WRITE.union(CommandFlags::MOVABLE_KEYS);
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("lpush", lpush, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("rpush", rpush, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("lpushx", lpushx, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("rpushx", rpushx, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("lpop", lpop, -2, WRITE_FAST, 1, 1, 1),
+    Command::new("rpop", rpop, -2, WRITE_FAST, 1, 1, 1),
+    Command::new("llen", llen, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("lrange", lrange, 4, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("lindex", lindex, 3, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("lset", lset, 4, CommandFlags::WRITE, 1, 1, 1),
+    Command::new("lrem", lrem, 4, CommandFlags::WRITE, 1, 1, 1),
+    Command::new("ltrim", ltrim, 4, CommandFlags::WRITE, 1, 1, 1),
+    Command::new("linsert", linsert, 5, CommandFlags::WRITE, 1, 1, 1),
+    Command::new("lpos", lpos, -3, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("lmove", lmove, 5, CommandFlags::WRITE, 1, 2, 1),
+    Command::new("rpoplpush", rpoplpush, 3, CommandFlags::WRITE, 1, 2, 1),
+    Command::new("lmpop", lmpop, -4, WRITE_MOVABLE_KEYS, 0, 0, 0),
+    Command::new("blpop", blpop, -3, WRITE_BLOCKING, 1, -2, 1),
+    Command::new("brpop", brpop, -3, WRITE_BLOCKING, 1, -2, 1),
+    Command::new("blmove", blmove, 6, WRITE_BLOCKING, 1, 2, 1),
+    Command::new(
+        "blmpop",
+        blmpop,
+        -5,
+        WRITE_BLOCKING.union(CommandFlags::MOVABLE_KEYS),
+        0,
+        0,
+        0,
+    ),
+];
+
+/// The list stored at `key`, if there is one.
+fn lookup_list<'a>(ctx: &'a mut Context, key: &[u8]) -> Result<Option<&'a List>, CommandError> {
+    match ctx.db.get(key) {
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn lookup_list_mut<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<&'a mut List>, CommandError> {
+    match ctx.db.get_mut(key) {
+        Some(Value::List(list)) => Ok(Some(list)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// The list stored at `key`, created empty if the key is missing.
+fn lookup_list_or_create<'a>(
+    ctx: &'a mut Context,
+    key: &Bytes,
+) -> Result<&'a mut List, CommandError> {
+    if !ctx.db.contains(key) {
+        let list = List::new(ctx.config.list_max_listpack_size);
+        ctx.db.set(key.clone(), Value::List(list), false);
+    }
+    let Some(list) = lookup_list_mut(ctx, key)? else {
+        unreachable!("key was just created");
+    };
+    Ok(list)
+}
+
+/// Deletes `key` if it holds a list that has been emptied, since Redis never
+/// keeps empty collections around.
+fn remove_if_empty(ctx: &mut Context, key: &[u8]) {
+    if matches!(ctx.db.get(key), Some(Value::List(list)) if list.is_empty()) {
+        ctx.db.remove(key);
+    }
+}
+
+/// Turns a possibly negative index into an offset from the head of a list of
+/// `len` entries, or `None` if it falls outside the list.
+fn resolve_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { index + len as i64 } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+/// Resolves a `start`/`stop` pair as LRANGE and LTRIM do, returning the
+/// inclusive range of offsets it covers, or `None` if it is empty.
+pub(super) fn resolve_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    (start <= stop && start < len).then_some((start as usize, stop as usize))
+}
+
+/// LPUSH/RPUSH key element [element ...], creating the list if needed.
+/// LPUSHX/RPUSHX (`existing_only`) leave missing keys alone.
+fn push(ctx: &mut Context, args: &[Bytes], front: bool, existing_only: bool) -> CommandResult {
+    let key = &args[1];
+    if existing_only && !ctx.db.contains(key) {
+        return Ok(RESP::Integer(0));
+    }
+    let list = lookup_list_or_create(ctx, key)?;
+    for element in &args[2..] {
+        list.push(element, front);
+    }
+    Ok(RESP::Integer(list.len() as i64))
+}
+
+fn lpush(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    push(ctx, args, true, false)
+}
+
+fn rpush(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    push(ctx, args, false, false)
+}
+
+fn lpushx(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    push(ctx, args, true, true)
+}
+
+fn rpushx(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    push(ctx, args, false, true)
+}
+
+/// LPOP/RPOP key [count]. Without a count the reply is a single element,
+/// with one it is an array of up to `count` elements.
+fn pop(ctx: &mut Context, args: &[Bytes], front: bool, command: &str) -> CommandResult {
+    let count = match args {
+        [_, _] => None,
+        [_, _, count] => match parse_i64(count)? {
+            count if count < 0 => {
+                return Err(CommandError::Message(
+                    "value is out of range, must be positive",
+                ))
+            }
+            count => Some(count as usize),
+        },
+        _ => return Err(CommandError::WrongArity(command.to_string())),
+    };
+
+    let key = &args[1];
+    let Some(list) = lookup_list_mut(ctx, key)? else {
+        return Ok(match count {
+            Some(_) => RESP::NullArray,
+            None => RESP::NullBulkString,
+        });
+    };
+
+    let reply = match count {
+        None => list
+            .pop(front)
+            .map_or(RESP::NullBulkString, RESP::BulkString),
+        Some(count) => RESP::Array(pop_many(list, front, count)),
+    };
+    remove_if_empty(ctx, key);
+    Ok(reply)
+}
+
+fn lpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    pop(ctx, args, true, "lpop")
+}
+
+fn rpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    pop(ctx, args, false, "rpop")
+}
+
+fn llen(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let len = lookup_list(ctx, &args[1])?.map_or(0, List::len);
+    Ok(RESP::Integer(len as i64))
+}
+
+/// LRANGE key start stop, both ends inclusive.
+fn lrange(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let start = parse_i64(&args[2])?;
+    let stop = parse_i64(&args[3])?;
+    let Some(list) = lookup_list(ctx, &args[1])? else {
+        return Ok(RESP::Array(Vec::new()));
+    };
+    let Some((start, stop)) = resolve_range(start, stop, list.len()) else {
+        return Ok(RESP::Array(Vec::new()));
+    };
+
+    Ok(RESP::Array(
+        list.iter_from(start)
+            .take(stop - start + 1)
+            .map(|element| RESP::BulkString(Bytes::copy_from_slice(element)))
+            .collect(),
+    ))
+}
+
+fn lindex(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let index = parse_i64(&args[2])?;
+    let element = lookup_list(ctx, &args[1])?.and_then(|list| {
+        let index = resolve_index(index, list.len())?;
+        list.get(index)
+    });
+    Ok(element.map_or(RESP::NullBulkString, |element| {
+        RESP::BulkString(Bytes::copy_from_slice(element))
+    }))
+}
+
+fn lset(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let index = parse_i64(&args[2])?;
+    let Some(list) = lookup_list_mut(ctx, &args[1])? else {
+        return Err(CommandError::Message("no such key"));
+    };
+    match resolve_index(index, list.len()) {
+        Some(index) if list.set(index, &args[3]) => Ok(RESP::SimpleString("OK".to_string())),
+        _ => Err(CommandError::Message("index out of range")),
+    }
+}
+
+/// LREM key count element. A positive `count` removes matches from the
+/// head, a negative one from the tail, and zero removes them all.
+fn lrem(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let count = parse_i64(&args[2])?;
+    let key = &args[1];
+    let Some(list) = lookup_list_mut(ctx, key)? else {
+        return Ok(RESP::Integer(0));
+    };
+    let removed = list.remove_matching(&args[3], count);
+    remove_if_empty(ctx, key);
+    Ok(RESP::Integer(removed as i64))
+}
+
+/// LTRIM key start stop keeps only the given range, deleting the key if the
+/// range is empty.
+fn ltrim(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let start = parse_i64(&args[2])?;
+    let stop = parse_i64(&args[3])?;
+    let key = &args[1];
+    let Some(list) = lookup_list_mut(ctx, key)? else {
+        return Ok(RESP::SimpleString("OK".to_string()));
+    };
+
+    let len = list.len();
+    match resolve_range(start, stop, len) {
+        Some((start, stop)) => {
+            list.remove_range(stop + 1, len - stop - 1);
+            list.remove_range(0, start);
+        }
+        None => list.remove_range(0, len),
+    }
+    remove_if_empty(ctx, key);
+    Ok(RESP::SimpleString("OK".to_string()))
+}
+
+/// LINSERT key BEFORE | AFTER pivot element. Replies with the new length,
+/// or -1 if the pivot is not in the list.
+fn linsert(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let after = match args[2].to_ascii_uppercase().as_slice() {
+        b"BEFORE" => false,
+        b"AFTER" => true,
+        _ => return Err(CommandError::Syntax),
+    };
+    let Some(list) = lookup_list_mut(ctx, &args[1])? else {
+        return Ok(RESP::Integer(0));
+    };
+
+    let pivot = &args[3];
+    let Some(index) = list.iter().position(|element| element == pivot) else {
+        return Ok(RESP::Integer(-1));
+    };
+    list.insert(index + after as usize, &args[4]);
+    Ok(RESP::Integer(list.len() as i64))
+}
+
+/// Pops up to `count` elements from one end of `list`.
+fn pop_many(list: &mut List, front: bool, count: usize) -> Vec<RESP> {
+    std::iter::from_fn(|| list.pop(front))
+        .take(count)
+        .map(RESP::BulkString)
+        .collect()
+}
+
+/// Parses a LEFT or RIGHT argument, returning whether it means the head.
+fn parse_end(arg: &[u8]) -> Result<bool, CommandError> {
+    match arg.to_ascii_uppercase().as_slice() {
+        b"LEFT" => Ok(true),
+        b"RIGHT" => Ok(false),
+        _ => Err(CommandError::Syntax),
+    }
+}
+
+/// Pops an element from one end of `source` and pushes it onto one end of
+/// `destination`, which may be the same list. Returns `None` if `source` is
+/// missing.
+fn move_element(
+    ctx: &mut Context,
+    source: &Bytes,
+    destination: &Bytes,
+    from_front: bool,
+    to_front: bool,
+) -> Result<Option<Bytes>, CommandError> {
+    if lookup_list(ctx, source)?.is_none() {
+        return Ok(None);
+    }
+    lookup_list(ctx, destination)?;
+
+    let Some(element) = lookup_list_mut(ctx, source)?.and_then(|list| list.pop(from_front)) else {
+        return Ok(None);
+    };
+    remove_if_empty(ctx, source);
+    lookup_list_or_create(ctx, destination)?.push(&element, to_front);
+    Ok(Some(element))
+}
+
+/// The parsed `numkeys key [key ...] LEFT | RIGHT [COUNT count]` arguments
+/// of LMPOP and BLMPOP.
+struct MultiPop<'a> {
+    keys: &'a [Bytes],
+    front: bool,
+    count: usize,
+}
+
+impl MultiPop<'_> {
+    fn parse(args: &[Bytes]) -> Result<MultiPop<'_>, CommandError> {
+        let numkeys = match parse_i64(&args[0]) {
+            Ok(numkeys) if numkeys > 0 => numkeys as usize,
+            _ => return Err(CommandError::Message("numkeys should be greater than 0")),
+        };
+        let Some(end) = args.get(numkeys + 1) else {
+            return Err(CommandError::Syntax);
+        };
+        let front = parse_end(end)?;
+
+        let count = match &args[numkeys + 2..] {
+            [] => 1,
+            [option, count] if option.eq_ignore_ascii_case(b"COUNT") => match parse_i64(count) {
+                Ok(count) if count > 0 => count as usize,
+                _ => return Err(CommandError::Message("count should be greater than 0")),
+            },
+            _ => return Err(CommandError::Syntax),
+        };
+
+        Ok(MultiPop {
+            keys: &args[1..=numkeys],
+            front,
+            count,
+        })
+    }
+
+    /// Pops from the first key holding a list, replying with its name and
+    /// the popped elements, or returns `None` if all the keys are missing.
+    fn pop(&self, ctx: &mut Context) -> Result<Option<RESP>, CommandError> {
+        for key in self.keys {
+            let Some(list) = lookup_list_mut(ctx, key)? else {
+                continue;
+            };
+            let elements = pop_many(list, self.front, self.count);
+            remove_if_empty(ctx, key);
+            return Ok(Some(RESP::Array(vec![
+                RESP::BulkString(key.clone()),
+                RESP::Array(elements),
+            ])));
+        }
+        Ok(None)
+    }
+}
+
+/// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+///
+/// Replies with the index of the first match, or with an array of the first
+/// `num-matches` indexes (all of them for 0) when COUNT is given. A negative
+/// RANK searches from the tail, skipping `|rank| - 1` matches either way,
+/// and MAXLEN caps how many elements are compared.
+fn lpos(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let mut rank: i64 = 1;
+    let mut count = None;
+    let mut maxlen = 0;
+
+    for pair in args[3..].chunks(2) {
+        let [option, value] = pair else {
+            return Err(CommandError::Syntax);
+        };
+        match option.to_ascii_uppercase().as_slice() {
+            b"RANK" => {
+                rank = parse_i64(value)?;
+                if rank == 0 {
+                    return Err(CommandError::Message(
+                        "RANK can't be zero: use 1 to start from the first match, 2 from the second ... or use negative to start from the end of the list",
+                    ));
+                }
+            }
+            b"COUNT" => match parse_i64(value)? {
+                count_value if count_value < 0 => {
+                    return Err(CommandError::Message("COUNT can't be negative"))
+                }
+                count_value => count = Some(count_value as usize),
+            },
+            b"MAXLEN" => match parse_i64(value)? {
+                len if len < 0 => return Err(CommandError::Message("MAXLEN can't be negative")),
+                len => maxlen = len as usize,
+            },
+            _ => return Err(CommandError::Syntax),
+        }
+    }
+
+    let element = &args[2];
+    let mut matches = Vec::new();
+    if let Some(list) = lookup_list(ctx, &args[1])? {
+        let len = list.len();
+        let scanned = if maxlen == 0 { len } else { maxlen.min(len) };
+        let wanted = match count {
+            Some(0) => usize::MAX,
+            Some(count) => count,
+            None => 1,
+        };
+        let skip = (rank.unsigned_abs() - 1).try_into().unwrap_or(usize::MAX);
+
+        let positions: Box<dyn Iterator<Item = (usize, &[u8])>> = if rank > 0 {
+            Box::new(list.iter().enumerate())
+        } else {
+            Box::new(list.iter().rev().enumerate().map(|(i, e)| (len - 1 - i, e)))
+        };
+        matches = positions
+            .take(scanned)
+            .filter(|(_, candidate)| candidate == element)
+            .skip(skip)
+            .take(wanted)
+            .map(|(index, _)| RESP::Integer(index as i64))
+            .collect();
+    }
+
+    Ok(match count {
+        Some(_) => RESP::Array(matches),
+        None => matches.pop().unwrap_or(RESP::NullBulkString),
+    })
+}
+
+/// LMOVE source destination LEFT | RIGHT LEFT | RIGHT
+fn lmove(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let from_front = parse_end(&args[3])?;
+    let to_front = parse_end(&args[4])?;
+    let element = move_element(ctx, &args[1], &args[2], from_front, to_front)?;
+    Ok(element.map_or(RESP::NullBulkString, RESP::BulkString))
+}
+
+/// RPOPLPUSH source destination, the same as LMOVE source destination
+/// RIGHT LEFT.
+fn rpoplpush(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let element = move_element(ctx, &args[1], &args[2], false, true)?;
+    Ok(element.map_or(RESP::NullBulkString, RESP::BulkString))
+}
+
+/// LMPOP numkeys key [key ...] LEFT | RIGHT [COUNT count]
+fn lmpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let pop = MultiPop::parse(&args[1..])?;
+    Ok(pop.pop(ctx)?.unwrap_or(RESP::NullArray))
+}
+
+/// Parks the client on `keys` until one of them holds a list or the
+/// deadline passes, at which point it gets a null reply.
+fn block_on_lists(ctx: &mut Context, keys: &[Bytes], deadline: Option<Instant>) -> CommandResult {
+    ctx.block = Some(Block {
+        keys: keys.to_vec(),
+        value_type: "list",
+        deadline,
+    });
+    Ok(RESP::NullArray)
+}
+
+/// BLPOP/BRPOP key [key ...] timeout. Pops from the first of the keys that
+/// holds a list, replying with the key and the element, or blocks until one
+/// of them does.
+fn blocking_pop(ctx: &mut Context, args: &[Bytes], front: bool) -> CommandResult {
+    let deadline = parse_timeout(&args[args.len() - 1])?;
+    let keys = &args[1..args.len() - 1];
+
+    for key in keys {
+        let Some(element) = lookup_list_mut(ctx, key)?.and_then(|list| list.pop(front)) else {
+            continue;
+        };
+        remove_if_empty(ctx, key);
+        return Ok(RESP::Array(vec![
+            RESP::BulkString(key.clone()),
+            RESP::BulkString(element),
+        ]));
+    }
+    block_on_lists(ctx, keys, deadline)
+}
+
+fn blpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    blocking_pop(ctx, args, true)
+}
+
+fn brpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    blocking_pop(ctx, args, false)
+}
+
+/// BLMOVE source destination LEFT | RIGHT LEFT | RIGHT timeout
+fn blmove(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let from_front = parse_end(&args[3])?;
+    let to_front = parse_end(&args[4])?;
+    let deadline = parse_timeout(&args[5])?;
+
+    match move_element(ctx, &args[1], &args[2], from_front, to_front)? {
+        Some(element) => Ok(RESP::BulkString(element)),
+        None => block_on_lists(ctx, &args[1..2], deadline),
+    }
+}
+
+/// BLMPOP timeout numkeys key [key ...] LEFT | RIGHT [COUNT count]
+fn blmpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let deadline = parse_timeout(&args[1])?;
+    let pop = MultiPop::parse(&args[2..])?;
+    match pop.pop(ctx)? {
+        Some(reply) => Ok(reply),
+        None => block_on_lists(ctx, pop.keys, deadline),
+    }
+}
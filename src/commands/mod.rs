@@ -0,0 +1,459 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    ops::BitOr,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    config::Config, db::Db, glob::glob_match, random::random_index, resp::RESP,
+    types::string_to_i64,
+};
+
+mod bitfield;
+mod bitmap;
+mod connection;
+mod hash;
+mod keys;
+mod list;
+mod server;
+mod set;
+mod string;
+mod zset;
+
+pub type CommandResult = Result<RESP, CommandError>;
+pub type CommandHandler = fn(&mut Context, &[Bytes]) -> CommandResult;
+
+/// Errors a command handler can fail with. The `Display` form is exactly the
+/// text sent back to the client as a RESP error.
+#[derive(Debug, thiserror::Error)]
+pub enum CommandError {
+    #[error("ERR unknown command '{0}', with args beginning with: {1}")]
+    UnknownCommand(String, String),
+    #[error("ERR unknown subcommand '{0}'. Try {1} HELP.")]
+    UnknownSubcommand(String, String),
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("ERR syntax error")]
+    Syntax,
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR invalid expire time in '{0}' command")]
+    InvalidExpireTime(&'static str),
+    #[error("ERR at least 1 input key is needed for '{0}' command")]
+    NoInputKeys(&'static str),
+    #[error("ERR Unsupported option {0}")]
+    UnsupportedOption(String),
+    #[error("ERR {0}")]
+    Message(&'static str),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CommandFlags(u8);
+
+impl CommandFlags {
+    pub const NONE: CommandFlags = CommandFlags(0);
+    pub const WRITE: CommandFlags = CommandFlags(1 << 0);
+    pub const READONLY: CommandFlags = CommandFlags(1 << 1);
+    pub const ADMIN: CommandFlags = CommandFlags(1 << 2);
+    pub const FAST: CommandFlags = CommandFlags(1 << 3);
+    pub const BLOCKING: CommandFlags = CommandFlags(1 << 4);
+    /// The keys can only be found by parsing the arguments, as with a
+    /// `numkeys` count.
+    pub const MOVABLE_KEYS: CommandFlags = CommandFlags(1 << 5);
+
+    const NAMES: &'static [(CommandFlags, &'static str)] = &[
+        (CommandFlags::WRITE, "write"),
+        (CommandFlags::READONLY, "readonly"),
+        (CommandFlags::ADMIN, "admin"),
+        (CommandFlags::FAST, "fast"),
+        (CommandFlags::BLOCKING, "blocking"),
+        (CommandFlags::MOVABLE_KEYS, "movablekeys"),
+    ];
+
+    pub const fn union(self, other: CommandFlags) -> CommandFlags {
+        CommandFlags(self.0 | other.0)
+    }
+
+    pub fn contains(self, other: CommandFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn names(self) -> impl Iterator<Item = &'static str> {
+        CommandFlags::NAMES
+            .iter()
+            .filter(move |(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+    }
+}
+
+impl BitOr for CommandFlags {
+    type Output = CommandFlags;
+
+    fn bitor(self, other: CommandFlags) -> CommandFlags {
+        self.union(other)
+    }
+}
+
+/// A command table entry.
+///
+/// `arity` follows Redis: a positive value is the exact number of arguments
+/// including the command name, a negative value is the minimum. `first_key`,
+/// `last_key` and `step` locate key arguments, with a negative `last_key`
+/// counting back from the end and zero meaning the command takes no keys.
+/// Commands flagged `MOVABLE_KEYS` have keys these cannot describe, and only
+/// give the ones at fixed positions, if any.
+pub struct Command {
+    pub name: &'static str,
+    pub handler: CommandHandler,
+    pub arity: i32,
+    pub flags: CommandFlags,
+    pub first_key: i32,
+    pub last_key: i32,
+    pub step: i32,
+}
+
+impl Command {
+    pub const fn new(
+        name: &'static str,
+        handler: CommandHandler,
+        arity: i32,
+        flags: CommandFlags,
+        first_key: i32,
+        last_key: i32,
+        step: i32,
+    ) -> Command {
+        Command {
+            name,
+            handler,
+            arity,
+            flags,
+            first_key,
+            last_key,
+            step,
+        }
+    }
+
+    fn accepts_arity(&self, argc: usize) -> bool {
+        let argc = argc as i32;
+        if self.arity < 0 {
+            argc >= -self.arity
+        } else {
+            argc == self.arity
+        }
+    }
+}
+
+pub struct CommandTable {
+    commands: HashMap<&'static str, &'static Command>,
+}
+
+impl CommandTable {
+    pub fn new() -> CommandTable {
+        let mut commands = HashMap::new();
+        let modules = [
+            connection::COMMANDS,
+            server::COMMANDS,
+            keys::COMMANDS,
+            string::COMMANDS,
+            bitmap::COMMANDS,
+            bitfield::COMMANDS,
+            list::COMMANDS,
+            hash::COMMANDS,
+            set::COMMANDS,
+            zset::COMMANDS,
+        ];
+        for command in modules.into_iter().flatten() {
+            commands.insert(command.name, command);
+        }
+        CommandTable { commands }
+    }
+
+    /// Looks up a command by name, ignoring ASCII case.
+    pub fn lookup(&self, name: &[u8]) -> Option<&'static Command> {
+        let name = std::str::from_utf8(name).ok()?.to_ascii_lowercase();
+        self.commands.get(name.as_str()).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'static Command> + '_ {
+        self.commands.values().copied()
+    }
+}
+
+/// State a command handler runs against.
+pub struct Context<'a> {
+    pub commands: &'a CommandTable,
+    pub config: &'a Config,
+    pub db: &'a mut Db,
+    /// Set by a blocking command that found nothing to serve. The client is
+    /// then parked and the command runs again once one of the keys is
+    /// created, while the reply it returned is only sent if the deadline
+    /// passes first.
+    pub block: Option<Block>,
+}
+
+/// What a blocked client is waiting for.
+pub struct Block {
+    pub keys: Vec<Bytes>,
+    /// The type name of the value the command can serve from, so that
+    /// creating one of the keys with another type does not wake the client.
+    pub value_type: &'static str,
+    /// `None` waits forever.
+    pub deadline: Option<Instant>,
+}
+
+/// Runs one client command and returns its reply, turning lookup, arity and
+/// handler failures into RESP errors.
+pub fn dispatch(ctx: &mut Context, args: &[Bytes]) -> RESP {
+    let result = match args.first().map(|name| (name, ctx.commands.lookup(name))) {
+        None => return RESP::Error("ERR empty command".to_string()),
+        Some((name, None)) => Err(unknown_command(name, &args[1..])),
+        Some((_, Some(command))) if !command.accepts_arity(args.len()) => {
+            Err(CommandError::WrongArity(command.name.to_string()))
+        }
+        Some((_, Some(command))) => (command.handler)(ctx, args),
+    };
+
+    result.unwrap_or_else(|e| RESP::Error(e.to_string()))
+}
+
+fn unknown_command(name: &[u8], args: &[Bytes]) -> CommandError {
+    const LIMIT: usize = 128;
+
+    let mut quoted = String::new();
+    for arg in args {
+        if quoted.len() >= LIMIT {
+            break;
+        }
+        let arg = String::from_utf8_lossy(arg);
+        let arg: String = arg.chars().take(LIMIT - quoted.len()).collect();
+        quoted.push_str(&format!("'{}' ", arg));
+    }
+
+    let name: String = String::from_utf8_lossy(name).chars().take(LIMIT).collect();
+    CommandError::UnknownCommand(name, quoted)
+}
+
+/// Parses an integer argument as strictly as Redis does.
+pub fn parse_i64(arg: &[u8]) -> Result<i64, CommandError> {
+    string_to_i64(arg).ok_or(CommandError::NotInteger)
+}
+
+/// Parses a floating point argument, rejecting NaN.
+pub fn parse_f64(arg: &[u8]) -> Result<f64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|arg| arg.parse::<f64>().ok())
+        .filter(|value| !value.is_nan())
+        .ok_or(CommandError::NotFloat)
+}
+
+/// Parses the timeout of a blocking command, given in seconds with up to
+/// millisecond precision, into a deadline. Zero means waiting forever.
+pub fn parse_timeout(arg: &[u8]) -> Result<Option<Instant>, CommandError> {
+    let seconds = parse_f64(arg)
+        .ok()
+        .filter(|seconds| seconds.is_finite())
+        .ok_or(CommandError::Message(
+            "timeout is not a float or out of range",
+        ))?;
+    if seconds < 0.0 {
+        return Err(CommandError::Message("timeout is negative"));
+    }
+    if seconds == 0.0 {
+        return Ok(None);
+    }
+    let timeout = Duration::try_from_secs_f64(seconds)
+        .ok()
+        .and_then(|timeout| Instant::now().checked_add(timeout))
+        .ok_or(CommandError::Message("timeout is out of range"))?;
+    Ok(Some(timeout))
+}
+
+/// Formats a float the way Redis replies with one: the shortest digits that
+/// parse back to the same value, in exponent notation when `%g` would use it
+/// (an exponent below -4 or of 17 and up), so `1e300` stays `1e+300` rather
+/// than printing 301 digits.
+pub fn format_f64(value: f64) -> String {
+    let scientific = format!("{:e}", value);
+    let Some((mantissa, exponent)) = scientific.split_once('e') else {
+        return scientific;
+    };
+    let exponent: i32 = exponent.parse().unwrap_or_default();
+    if (-4..17).contains(&exponent) {
+        format!("{}", value)
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, sign, exponent.unsigned_abs())
+    }
+}
+
+/// Picks random entries for SRANDMEMBER, HRANDFIELD and ZRANDMEMBER. A
+/// negative `count` picks `-count` entries that may repeat, a positive one
+/// up to `count` distinct entries, told apart by `key`. `entries` yields
+/// all `len` of them and `random_entry` picks one.
+pub fn random_sample<T, K: Eq + Hash>(
+    count: i64,
+    len: usize,
+    entries: impl Iterator<Item = T>,
+    mut random_entry: impl FnMut() -> Option<T>,
+    key: impl Fn(&T) -> K,
+) -> Vec<T> {
+    if count < 0 {
+        return (0..count.unsigned_abs())
+            .filter_map(|_| random_entry())
+            .collect();
+    }
+    let count = count as usize;
+    if count >= len {
+        return entries.collect();
+    }
+
+    if count * 3 > len {
+        // Most entries are wanted, so drop random ones from a full copy
+        // rather than retrying picks that were already made.
+        let mut sample: Vec<T> = entries.collect();
+        while sample.len() > count {
+            sample.swap_remove(random_index(sample.len()));
+        }
+        return sample;
+    }
+    let mut seen = HashSet::new();
+    let mut sample = Vec::with_capacity(count);
+    while sample.len() < count {
+        let Some(entry) = random_entry() else {
+            break;
+        };
+        if seen.insert(key(&entry)) {
+            sample.push(entry);
+        }
+    }
+    sample
+}
+
+/// The arguments the SCAN family of commands share: a cursor followed by
+/// `[MATCH pattern] [COUNT count]`, plus `NOVALUES` where the command
+/// allows it.
+pub struct ScanArgs<'a> {
+    pub cursor: u64,
+    pub pattern: Option<&'a [u8]>,
+    pub count: usize,
+    pub novalues: bool,
+}
+
+impl<'a> ScanArgs<'a> {
+    /// Parses `cursor [option ...]`, starting at the cursor argument.
+    pub fn parse(args: &'a [Bytes], allow_novalues: bool) -> Result<ScanArgs<'a>, CommandError> {
+        let cursor = std::str::from_utf8(&args[0])
+            .ok()
+            .and_then(|cursor| cursor.parse().ok())
+            .ok_or(CommandError::Message("invalid cursor"))?;
+        let mut scan = ScanArgs {
+            cursor,
+            pattern: None,
+            count: 10,
+            novalues: false,
+        };
+
+        let mut options = args[1..].iter();
+        while let Some(option) = options.next() {
+            match option.to_ascii_uppercase().as_slice() {
+                b"MATCH" => {
+                    let pattern = options.next().ok_or(CommandError::Syntax)?;
+                    // Everything matches `*`, so don't bother checking.
+                    scan.pattern = (&pattern[..] != b"*").then_some(&pattern[..]);
+                }
+                b"COUNT" => {
+                    let count = parse_i64(options.next().ok_or(CommandError::Syntax)?)?;
+                    if count < 1 {
+                        return Err(CommandError::Syntax);
+                    }
+                    scan.count = count as usize;
+                }
+                b"NOVALUES" if allow_novalues => scan.novalues = true,
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+        Ok(scan)
+    }
+
+    /// Whether `item` passes the MATCH filter.
+    pub fn matches(&self, item: &[u8]) -> bool {
+        match self.pattern {
+            Some(pattern) => glob_match(pattern, item, false),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Runs a command against `db` with the default config and returns the
+    /// wire encoding of its reply.
+    pub(crate) fn call(db: &mut Db, args: &[&str]) -> Vec<u8> {
+        let commands = CommandTable::new();
+        let config = Config::default();
+        let mut ctx = Context {
+            commands: &commands,
+            config: &config,
+            db,
+            block: None,
+        };
+        let args: Vec<Bytes> = args
+            .iter()
+            .map(|arg| Bytes::copy_from_slice(arg.as_bytes()))
+            .collect();
+        let mut reply = Vec::new();
+        dispatch(&mut ctx, &args).write_to(&mut reply);
+        reply
+    }
+
+    #[test]
+    fn command_info_flags_movable_keys() {
+        let mut db = Db::default();
+        assert_eq!(
+            call(&mut db, &["COMMAND", "INFO", "lmpop"]),
+            b"*1\r\n*6\r\n$5\r\nlmpop\r\n:-4\r\n*2\r\n+write\r\n+movablekeys\r\n:0\r\n:0\r\n:0\r\n"
+        );
+    }
+
+    #[test]
+    fn formats_floats_like_redis() {
+        let cases: &[(f64, &str)] = &[
+            (0.0, "0"),
+            (-0.0, "-0"),
+            (3.0, "3"),
+            (-2.5, "-2.5"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (1.0 / 3.0, "0.3333333333333333"),
+            (0.0001, "0.0001"),
+            (0.00001, "1e-05"),
+            (1.5e-300, "1.5e-300"),
+            (1e16, "10000000000000000"),
+            (1e17, "1e+17"),
+            (1e300, "1e+300"),
+            (-1.2345e21, "-1.2345e+21"),
+            (f64::MAX, "1.7976931348623157e+308"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ];
+        for &(value, expected) in cases {
+            assert_eq!(format_f64(value), expected);
+            if value.is_finite() {
+                assert_eq!(expected.parse::<f64>().unwrap(), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use bytes::Bytes;
+
+use super::{Command, CommandError, CommandFlags, CommandResult, Context};
+use crate::resp::RESP;
+
+pub(super) const COMMANDS: &[Command] = &[Command::new(
+    "config",
+    config,
+    -2,
+    CommandFlags::ADMIN,
+    0,
+    0,
+    0,
+)];
+
+fn config(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    match args[1].to_ascii_uppercase().as_slice() {
+        b"GET" if args.len() > 2 => {
+            let patterns: Vec<&[u8]> = args[2..].iter().map(|p| p.as_ref()).collect();
+            Ok(RESP::Array(
+                ctx.config
+                    .matching(&patterns)
+                    .into_iter()
+                    .flat_map(|(name, value)| {
+                        [
+                            RESP::BulkString(Bytes::from_static(name.as_bytes())),
+                            RESP::BulkString(Bytes::from(value)),
+                        ]
+                    })
+                    .collect(),
+            ))
+        }
+        b"GET" => Err(CommandError::WrongArity("config|get".to_string())),
+        _ => Err(CommandError::UnknownSubcommand(
+            String::from_utf8_lossy(&args[1]).into_owned(),
+            "CONFIG".to_string(),
+        )),
+    }
+}
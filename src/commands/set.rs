@@ -0,0 +1,509 @@
+use std::collections::HashSet;
+
+use bytes::Bytes;
+
+use super::{
+    parse_i64, random_sample, Command, CommandError, CommandFlags, CommandResult, Context, ScanArgs,
+};
+use crate::{db::Value, resp::RESP, types::Set};
+
+const READONLY_FAST: CommandFlags = CommandFlags::READONLY.union(CommandFlags::FAST);
+const WRITE_FAST: CommandFlags = CommandFlags::WRITE.union(CommandFlags::FAST);
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("sadd", sadd, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("srem", srem, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("smembers", smembers, 2, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("sismember", sismember, 3, READONLY_FAST, 1, 1, 1),
+    Command::new("smismember", smismember, -3, READONLY_FAST, 1, 1, 1),
+    Command::new("scard", scard, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("spop", spop, -2, WRITE_FAST, 1, 1, 1),
+    Command::new(
+        "srandmember",
+        srandmember,
+        -2,
+        CommandFlags::READONLY,
+        1,
+        1,
+        1,
+    ),
+    Command::new("smove", smove, 4, WRITE_FAST, 1, 2, 1),
+    Command::new("sinter", sinter, -2, CommandFlags::READONLY, 1, -1, 1),
+    Command::new("sunion", sunion, -2, CommandFlags::READONLY, 1, -1, 1),
+    Command::new("sdiff", sdiff, -2, CommandFlags::READONLY, 1, -1, 1),
+    Command::new(
+        "sinterstore",
+        sinterstore,
+        -3,
+        CommandFlags::WRITE,
+        1,
+        -1,
+        1,
+    ),
+    Command::new(
+        "sunionstore",
+        sunionstore,
+        -3,
+        CommandFlags::WRITE,
+        1,
+        -1,
+        1,
+    ),
+    Command::new("sdiffstore", sdiffstore, -3, CommandFlags::WRITE, 1, -1, 1),
+    Command::new(
+        "sintercard",
+        sintercard,
+        -3,
+        CommandFlags::READONLY.union(CommandFlags::MOVABLE_KEYS),
+        0,
+        0,
+        0,
+    ),
+    Command::new("sscan", sscan, -3, CommandFlags::READONLY, 1, 1, 1),
+];
+
+/// The set stored at `key`, if there is one.
+fn lookup_set<'a>(ctx: &'a mut Context, key: &[u8]) -> Result<Option<&'a Set>, CommandError> {
+    match ctx.db.get(key) {
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn lookup_set_mut<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<&'a mut Set>, CommandError> {
+    match ctx.db.get_mut(key) {
+        Some(Value::Set(set)) => Ok(Some(set)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// The set stored at `key`, created empty if the key is missing.
+fn lookup_set_or_create<'a>(
+    ctx: &'a mut Context,
+    key: &Bytes,
+) -> Result<&'a mut Set, CommandError> {
+    if !ctx.db.contains(key) {
+        let set = Set::new(ctx.config.set_max_intset_entries);
+        ctx.db.set(key.clone(), Value::Set(set), false);
+    }
+    let Some(set) = lookup_set_mut(ctx, key)? else {
+        unreachable!("key was just created");
+    };
+    Ok(set)
+}
+
+/// Deletes `key` if it holds a set that has been emptied.
+fn remove_if_empty(ctx: &mut Context, key: &[u8]) {
+    if matches!(ctx.db.get(key), Some(Value::Set(set)) if set.is_empty()) {
+        ctx.db.remove(key);
+    }
+}
+
+fn members_reply(members: impl Iterator<Item = Bytes>) -> RESP {
+    RESP::Array(members.map(RESP::BulkString).collect())
+}
+
+fn sadd(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let set = lookup_set_or_create(ctx, &args[1])?;
+    let added = args[2..].iter().filter(|member| set.insert(member)).count();
+    Ok(RESP::Integer(added as i64))
+}
+
+fn srem(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(set) = lookup_set_mut(ctx, &args[1])? else {
+        return Ok(RESP::Integer(0));
+    };
+    let removed = args[2..].iter().filter(|member| set.remove(member)).count();
+    remove_if_empty(ctx, &args[1]);
+    Ok(RESP::Integer(removed as i64))
+}
+
+fn smembers(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(set) = lookup_set(ctx, &args[1])? else {
+        return Ok(RESP::Array(vec![]));
+    };
+    Ok(members_reply(set.iter()))
+}
+
+fn sismember(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let found = lookup_set(ctx, &args[1])?.is_some_and(|set| set.contains(&args[2]));
+    Ok(RESP::Integer(found as i64))
+}
+
+fn smismember(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let set = lookup_set(ctx, &args[1])?;
+    let found = args[2..]
+        .iter()
+        .map(|member| RESP::Integer(set.is_some_and(|set| set.contains(member)) as i64))
+        .collect();
+    Ok(RESP::Array(found))
+}
+
+fn scard(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let len = lookup_set(ctx, &args[1])?.map_or(0, Set::len);
+    Ok(RESP::Integer(len as i64))
+}
+
+/// SPOP key [count]. Without a count the reply is a single member, with one
+/// it is an array of up to `count` distinct members.
+fn spop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let count = match args {
+        [_, _] => None,
+        [_, _, count] => match parse_i64(count)? {
+            count if count >= 0 => Some(count as usize),
+            _ => {
+                return Err(CommandError::Message(
+                    "value is out of range, must be positive",
+                ))
+            }
+        },
+        _ => return Err(CommandError::Syntax),
+    };
+
+    let key = &args[1];
+    let Some(set) = lookup_set_mut(ctx, key)? else {
+        return Ok(match count {
+            None => RESP::NullBulkString,
+            Some(_) => RESP::Array(vec![]),
+        });
+    };
+    let reply = match count {
+        None => set
+            .pop_random()
+            .map_or(RESP::NullBulkString, RESP::BulkString),
+        Some(count) if count >= set.len() => {
+            let members = members_reply(set.iter());
+            ctx.db.remove(key);
+            return Ok(members);
+        }
+        Some(count) => members_reply((0..count).filter_map(|_| set.pop_random())),
+    };
+    remove_if_empty(ctx, key);
+    Ok(reply)
+}
+
+/// SRANDMEMBER key [count], sampled by `random_sample`.
+fn srandmember(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let count = match args {
+        [_, _] => None,
+        [_, _, count] => Some(parse_i64(count)?),
+        _ => return Err(CommandError::Syntax),
+    };
+    if count.is_some_and(|count| count.unsigned_abs() > i64::MAX as u64 / 2) {
+        return Err(CommandError::Message("value is out of range"));
+    }
+
+    let set = lookup_set(ctx, &args[1])?;
+    let Some(count) = count else {
+        let member = set.and_then(Set::random_member);
+        return Ok(member.map_or(RESP::NullBulkString, RESP::BulkString));
+    };
+    let Some(set) = set else {
+        return Ok(RESP::Array(vec![]));
+    };
+
+    let members = random_sample(
+        count,
+        set.len(),
+        set.iter(),
+        || set.random_member(),
+        Bytes::clone,
+    );
+    Ok(members_reply(members.into_iter()))
+}
+
+/// SMOVE source destination member
+fn smove(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let (source, destination, member) = (&args[1], &args[2], &args[3]);
+    if lookup_set(ctx, source)?.is_none() {
+        return Ok(RESP::Integer(0));
+    }
+    lookup_set(ctx, destination)?;
+    let Some(set) = lookup_set_mut(ctx, source)? else {
+        unreachable!("source was just looked up");
+    };
+    if source == destination {
+        return Ok(RESP::Integer(set.contains(member) as i64));
+    }
+    if !set.remove(member) {
+        return Ok(RESP::Integer(0));
+    }
+    remove_if_empty(ctx, source);
+    lookup_set_or_create(ctx, destination)?.insert(member);
+    Ok(RESP::Integer(1))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Inter,
+    Union,
+    Diff,
+}
+
+/// Computes the intersection, union or difference of the sets at `keys`,
+/// with missing keys counting as empty sets. Every key is type checked
+/// before anything is computed.
+fn combine(
+    ctx: &mut Context,
+    keys: &[Bytes],
+    operation: Operation,
+) -> Result<Vec<Bytes>, CommandError> {
+    let mut lens = Vec::with_capacity(keys.len());
+    for key in keys {
+        lens.push(lookup_set(ctx, key)?.map(Set::len));
+    }
+
+    match operation {
+        Operation::Inter => {
+            if lens.contains(&None) {
+                return Ok(vec![]);
+            }
+            // Start from the smallest set, as the result is no bigger.
+            let smallest = (0..keys.len()).min_by_key(|&i| lens[i]).unwrap_or(0);
+            let mut members = members_of(ctx, &keys[smallest])?;
+            for (i, key) in keys.iter().enumerate() {
+                if i != smallest && !members.is_empty() {
+                    let set = lookup_set(ctx, key)?;
+                    members.retain(|member| set.is_some_and(|set| set.contains(member)));
+                }
+            }
+            Ok(members)
+        }
+        Operation::Union => {
+            let mut seen = HashSet::new();
+            let mut members = Vec::new();
+            for key in keys {
+                for member in members_of(ctx, key)? {
+                    if seen.insert(member.clone()) {
+                        members.push(member);
+                    }
+                }
+            }
+            Ok(members)
+        }
+        Operation::Diff => {
+            let mut members = members_of(ctx, &keys[0])?;
+            for key in &keys[1..] {
+                if members.is_empty() {
+                    break;
+                }
+                if let Some(set) = lookup_set(ctx, key)? {
+                    members.retain(|member| !set.contains(member));
+                }
+            }
+            Ok(members)
+        }
+    }
+}
+
+fn members_of(ctx: &mut Context, key: &[u8]) -> Result<Vec<Bytes>, CommandError> {
+    Ok(lookup_set(ctx, key)?.map_or_else(Vec::new, |set| set.iter().collect()))
+}
+
+fn sinter(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    Ok(members_reply(
+        combine(ctx, &args[1..], Operation::Inter)?.into_iter(),
+    ))
+}
+
+fn sunion(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    Ok(members_reply(
+        combine(ctx, &args[1..], Operation::Union)?.into_iter(),
+    ))
+}
+
+fn sdiff(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    Ok(members_reply(
+        combine(ctx, &args[1..], Operation::Diff)?.into_iter(),
+    ))
+}
+
+/// SINTERSTORE/SUNIONSTORE/SDIFFSTORE destination key [key ...], replacing
+/// the destination with the result, or deleting it if the result is empty.
+fn store(ctx: &mut Context, args: &[Bytes], operation: Operation) -> CommandResult {
+    let members = combine(ctx, &args[2..], operation)?;
+    let destination = &args[1];
+    if members.is_empty() {
+        ctx.db.remove(destination);
+        return Ok(RESP::Integer(0));
+    }
+    let mut set = Set::new(ctx.config.set_max_intset_entries);
+    for member in &members {
+        set.insert(member);
+    }
+    ctx.db.set(destination.clone(), Value::Set(set), false);
+    Ok(RESP::Integer(members.len() as i64))
+}
+
+fn sinterstore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    store(ctx, args, Operation::Inter)
+}
+
+fn sunionstore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    store(ctx, args, Operation::Union)
+}
+
+fn sdiffstore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    store(ctx, args, Operation::Diff)
+}
+
+/// SINTERCARD numkeys key [key ...] [LIMIT limit], where a limit of 0 means
+/// no limit.
+fn sintercard(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let numkeys = match parse_i64(&args[1]) {
+        Ok(numkeys) if numkeys > 0 => numkeys as usize,
+        _ => return Err(CommandError::Message("numkeys should be greater than 0")),
+    };
+    if numkeys > args.len() - 2 {
+        return Err(CommandError::Message(
+            "Number of keys can't be greater than number of args",
+        ));
+    }
+
+    let limit = match &args[numkeys + 2..] {
+        [] => 0,
+        [option, limit] if option.eq_ignore_ascii_case(b"LIMIT") => match parse_i64(limit) {
+            Ok(limit) if limit >= 0 => limit as usize,
+            _ => return Err(CommandError::Message("LIMIT can't be negative")),
+        },
+        _ => return Err(CommandError::Syntax),
+    };
+
+    // Every key is type checked before a missing one settles the result.
+    let mut sets = Vec::with_capacity(numkeys);
+    for value in ctx.db.get_many(&args[2..numkeys + 2]) {
+        match value {
+            Some(Value::Set(set)) => sets.push(Some(set)),
+            Some(_) => return Err(CommandError::WrongType),
+            None => sets.push(None),
+        }
+    }
+    let Some(mut sets) = sets.into_iter().collect::<Option<Vec<&Set>>>() else {
+        return Ok(RESP::Integer(0));
+    };
+
+    sets.sort_by_key(|set| set.len());
+    let count = intersection_card(sets[0].iter(), &sets[1..], limit);
+    Ok(RESP::Integer(count as i64))
+}
+
+/// Counts the `candidates`, the members of the smallest set, that all of
+/// `others` hold, stopping as soon as the count reaches `limit` unless it is
+/// 0.
+fn intersection_card(
+    candidates: impl Iterator<Item = Bytes>,
+    others: &[&Set],
+    limit: usize,
+) -> usize {
+    let mut count = 0;
+    for member in candidates {
+        if others.iter().all(|set| set.contains(&member)) {
+            count += 1;
+            if count == limit {
+                break;
+            }
+        }
+    }
+    count
+}
+
+/// SSCAN key cursor [MATCH pattern] [COUNT count]
+fn sscan(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let scan = ScanArgs::parse(&args[2..], false)?;
+    let mut members = Vec::new();
+    let cursor = match lookup_set(ctx, &args[1])? {
+        Some(set) => set.scan(scan.cursor, scan.count, |member| {
+            if scan.matches(member) {
+                members.push(RESP::BulkString(Bytes::copy_from_slice(member)));
+            }
+        }),
+        None => 0,
+    };
+    Ok(RESP::Array(vec![
+        RESP::BulkString(Bytes::from(cursor.to_string())),
+        RESP::Array(members),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{commands::tests::call, db::Db};
+
+    #[test]
+    fn intersection_card_stops_at_the_limit() {
+        let mut set = Set::new(512);
+        for n in 0..1000 {
+            set.insert(n.to_string().as_bytes());
+        }
+
+        let mut visited = 0;
+        let candidates = set.iter().inspect(|_| visited += 1);
+        assert_eq!(intersection_card(candidates, &[&set], 5), 5);
+        assert_eq!(visited, 5);
+
+        let mut visited = 0;
+        let candidates = set.iter().inspect(|_| visited += 1);
+        assert_eq!(intersection_card(candidates, &[&set], 0), 1000);
+        assert_eq!(visited, 1000);
+    }
+
+    #[test]
+    fn sintercard_counts_up_to_the_limit() {
+        let mut db = Db::default();
+        call(&mut db, &["SADD", "a", "1", "2", "3", "x"]);
+        call(&mut db, &["SADD", "b", "2", "3", "4", "x", "y"]);
+        assert_eq!(call(&mut db, &["SINTERCARD", "2", "a", "b"]), b":3\r\n");
+        assert_eq!(
+            call(&mut db, &["SINTERCARD", "2", "a", "b", "LIMIT", "2"]),
+            b":2\r\n"
+        );
+        assert_eq!(
+            call(&mut db, &["SINTERCARD", "2", "a", "b", "LIMIT", "0"]),
+            b":3\r\n"
+        );
+        assert_eq!(
+            call(&mut db, &["SINTERCARD", "2", "a", "missing"]),
+            b":0\r\n"
+        );
+    }
+
+    #[test]
+    fn spop_tells_a_negative_count_from_a_non_integer_one() {
+        let mut db = Db::default();
+        call(&mut db, &["SADD", "s", "a"]);
+        assert_eq!(
+            call(&mut db, &["SPOP", "s", "-1"]),
+            b"-ERR value is out of range, must be positive\r\n"
+        );
+        for count in ["one", "1.5", "99999999999999999999"] {
+            assert_eq!(
+                call(&mut db, &["SPOP", "s", count]),
+                b"-ERR value is not an integer or out of range\r\n"
+            );
+        }
+        assert_eq!(call(&mut db, &["SPOP", "s", "0"]), b"*0\r\n");
+        assert_eq!(call(&mut db, &["SCARD", "s"]), b":1\r\n");
+    }
+
+    #[test]
+    fn sintercard_wrong_type_wins_over_a_missing_key() {
+        let mut db = Db::default();
+        call(&mut db, &["SADD", "a", "1"]);
+        call(&mut db, &["SET", "s", "1"]);
+        let wrong_type = b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n";
+        assert_eq!(
+            call(&mut db, &["SINTERCARD", "3", "missing", "a", "s"]),
+            wrong_type
+        );
+        assert_eq!(
+            call(&mut db, &["SINTERCARD", "2", "s", "missing"]),
+            wrong_type
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use bytes::Bytes;
+
+use super::{
+    format_f64, parse_f64, parse_i64, Command, CommandError, CommandFlags, CommandResult, Context,
+};
+use crate::{
+    db::{now_ms, Value},
+    resp::RESP,
+    types::StringValue,
+};
+
+const READONLY_FAST: CommandFlags = CommandFlags::READONLY.union(CommandFlags::FAST);
+const WRITE_FAST: CommandFlags = CommandFlags::WRITE.union(CommandFlags::FAST);
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("get", get, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("set", set, -3, CommandFlags::WRITE, 1, 1, 1),
+    Command::new("incr", incr, 2, WRITE_FAST, 1, 1, 1),
+    Command::new("decr", decr, 2, WRITE_FAST, 1, 1, 1),
+    Command::new("incrby", incrby, 3, WRITE_FAST, 1, 1, 1),
+    Command::new("decrby", decrby, 3, WRITE_FAST, 1, 1, 1),
+    Command::new("incrbyfloat", incrbyfloat, 3, WRITE_FAST, 1, 1, 1),
+    Command::new("append", append, 3, WRITE_FAST, 1, 1, 1),
+    Command::new("getrange", getrange, 4, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("setrange", setrange, 4, CommandFlags::WRITE, 1, 1, 1),
+    Command::new("strlen", strlen, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("getdel", getdel, 2, WRITE_FAST, 1, 1, 1),
+    Command::new("getex", getex, -2, WRITE_FAST, 1, 1, 1),
+    Command::new("mget", mget, -2, READONLY_FAST, 1, -1, 1),
+    Command::new("mset", mset, -3, CommandFlags::WRITE, 1, -1, 2),
+    Command::new("msetnx", msetnx, -3, CommandFlags::WRITE, 1, -1, 2),
+];
+
+/// Largest string a command may build, matching Redis' default
+/// `proto-max-bulk-len`.
+const MAX_STRING_LENGTH: usize = 512 * 1024 * 1024;
+
+/// The string stored at `key`, if there is one.
+fn lookup_string<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<&'a StringValue>, CommandError> {
+    match ctx.db.get(key) {
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn lookup_string_mut<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<&'a mut StringValue>, CommandError> {
+    match ctx.db.get_mut(key) {
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn check_string_length(length: usize) -> Result<(), CommandError> {
+    if length > MAX_STRING_LENGTH {
+        return Err(CommandError::Message(
+            "string exceeds maximum allowed size (proto-max-bulk-len)",
+        ));
+    }
+    Ok(())
+}
+
+/// Turns an `EX`/`PX`/`EXAT`/`PXAT` option and its argument into an absolute
+/// expiry time in unix milliseconds.
+pub(super) fn parse_expire_option(
+    option: &[u8],
+    value: Option<&Bytes>,
+    command: &'static str,
+) -> Result<u64, CommandError> {
+    let value = parse_i64(value.ok_or(CommandError::Syntax)?)?;
+    if value <= 0 {
+        return Err(CommandError::InvalidExpireTime(command));
+    }
+    let (millis, base) = match option {
+        b"EX" => (value.checked_mul(1000), now_ms() as i64),
+        b"PX" => (Some(value), now_ms() as i64),
+        b"EXAT" => (value.checked_mul(1000), 0),
+        _ => (Some(value), 0),
+    };
+    let when = millis
+        .and_then(|millis| millis.checked_add(base))
+        .ok_or(CommandError::InvalidExpireTime(command))?;
+    Ok(when as u64)
+}
+
+fn get(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    Ok(match lookup_string(ctx, &args[1])? {
+        Some(value) => RESP::BulkString(value.to_bytes()),
+        None => RESP::NullBulkString,
+    })
+}
+
+#[derive(PartialEq, Eq)]
+enum SetCondition {
+    Always,
+    IfMissing,
+    IfExists,
+}
+
+/// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds |
+/// EXAT unix-time-seconds | PXAT unix-time-milliseconds | KEEPTTL]
+fn set(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let mut condition = SetCondition::Always;
+    let mut get = false;
+    let mut keep_ttl = false;
+    let mut expire_at = None;
+
+    let mut options = args[3..].iter();
+    while let Some(option) = options.next() {
+        let option = option.to_ascii_uppercase();
+        match option.as_slice() {
+            b"NX" if condition != SetCondition::IfExists => condition = SetCondition::IfMissing,
+            b"XX" if condition != SetCondition::IfMissing => condition = SetCondition::IfExists,
+            b"GET" => get = true,
+            b"KEEPTTL" if expire_at.is_none() => keep_ttl = true,
+            b"EX" | b"PX" | b"EXAT" | b"PXAT" if !keep_ttl && expire_at.is_none() => {
+                expire_at = Some(parse_expire_option(&option, options.next(), "set")?);
+            }
+            _ => return Err(CommandError::Syntax),
+        }
+    }
+
+    // SET overwrites keys of any type, but GET can only return a string.
+    let key = &args[1];
+    let old = match get {
+        true => lookup_string(ctx, key)?.map(StringValue::to_bytes),
+        false => None,
+    };
+    let exists = old.is_some() || ctx.db.contains(key);
+
+    let allowed = match condition {
+        SetCondition::Always => true,
+        SetCondition::IfMissing => !exists,
+        SetCondition::IfExists => exists,
+    };
+    if allowed {
+        ctx.db.set(
+            key.clone(),
+            Value::String(StringValue::from_bytes(args[2].clone())),
+            keep_ttl,
+        );
+        if let Some(when) = expire_at {
+            ctx.db.set_expiry(key, when);
+        }
+    }
+
+    Ok(match (get, old) {
+        (true, Some(old)) => RESP::BulkString(old),
+        (true, None) => RESP::NullBulkString,
+        (false, _) if allowed => RESP::SimpleString("OK".to_string()),
+        (false, _) => RESP::NullBulkString,
+    })
+}
+
+fn incr(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    incr_decr(ctx, &args[1], 1)
+}
+
+fn decr(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    incr_decr(ctx, &args[1], -1)
+}
+
+fn incrby(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    incr_decr(ctx, &args[1], parse_i64(&args[2])?)
+}
+
+fn decrby(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let decrement = parse_i64(&args[2])?;
+    let delta = decrement
+        .checked_neg()
+        .ok_or(CommandError::Message("decrement would overflow"))?;
+    incr_decr(ctx, &args[1], delta)
+}
+
+/// Adds `delta` to the integer stored at `key`, treating a missing key as 0.
+/// The key keeps its time to live.
+fn incr_decr(ctx: &mut Context, key: &Bytes, delta: i64) -> CommandResult {
+    let current = match lookup_string(ctx, key)? {
+        Some(value) => value.as_int().ok_or(CommandError::NotInteger)?,
+        None => 0,
+    };
+    let value = current.checked_add(delta).ok_or(CommandError::Message(
+        "increment or decrement would overflow",
+    ))?;
+
+    ctx.db
+        .set(key.clone(), Value::String(StringValue::Int(value)), true);
+    Ok(RESP::Integer(value))
+}
+
+fn incrbyfloat(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let key = &args[1];
+    let increment = parse_f64(&args[2])?;
+    let current = match lookup_string(ctx, key)? {
+        Some(value) => parse_f64(&value.as_bytes())?,
+        None => 0.0,
+    };
+
+    let value = current + increment;
+    if !value.is_finite() {
+        return Err(CommandError::Message(
+            "increment would produce NaN or Infinity",
+        ));
+    }
+
+    let formatted = Bytes::from(format_f64(value));
+    ctx.db.set(
+        key.clone(),
+        Value::String(StringValue::from_bytes(formatted.clone())),
+        true,
+    );
+    Ok(RESP::BulkString(formatted))
+}
+
+fn append(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let (key, suffix) = (&args[1], &args[2]);
+    match lookup_string_mut(ctx, key)? {
+        Some(value) => {
+            check_string_length(value.len() + suffix.len())?;
+            let buffer = value.make_mut();
+            buffer.extend_from_slice(suffix);
+            Ok(RESP::Integer(buffer.len() as i64))
+        }
+        None => {
+            let value = StringValue::from_bytes(suffix.clone());
+            ctx.db.set(key.clone(), Value::String(value), false);
+            Ok(RESP::Integer(suffix.len() as i64))
+        }
+    }
+}
+
+/// GETRANGE key start end, with negative offsets counting from the end.
+fn getrange(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let start = parse_i64(&args[2])?;
+    let end = parse_i64(&args[3])?;
+    let Some(value) = lookup_string(ctx, &args[1])? else {
+        return Ok(RESP::BulkString(Bytes::new()));
+    };
+
+    let bytes = value.as_bytes();
+    let length = bytes.len() as i64;
+    if start < 0 && end < 0 && start > end {
+        return Ok(RESP::BulkString(Bytes::new()));
+    }
+    let resolve = |index: i64| {
+        if index < 0 {
+            (length + index).max(0)
+        } else {
+            index
+        }
+    };
+    let start = resolve(start);
+    let end = resolve(end).min(length - 1);
+    if start > end || length == 0 {
+        return Ok(RESP::BulkString(Bytes::new()));
+    }
+
+    Ok(RESP::BulkString(Bytes::copy_from_slice(
+        &bytes[start as usize..=end as usize],
+    )))
+}
+
+/// SETRANGE key offset value, zero-padding the string if it is shorter than
+/// `offset`.
+fn setrange(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let (key, patch) = (&args[1], &args[3]);
+    let offset = parse_i64(&args[2])?;
+    if offset < 0 {
+        return Err(CommandError::Message("offset is out of range"));
+    }
+    let offset = offset as usize;
+
+    let Some(value) = lookup_string_mut(ctx, key)? else {
+        if patch.is_empty() {
+            return Ok(RESP::Integer(0));
+        }
+        check_string_length(offset + patch.len())?;
+        let mut buffer = vec![0; offset];
+        buffer.extend_from_slice(patch);
+        let length = buffer.len();
+        ctx.db.set(
+            key.clone(),
+            Value::String(StringValue::Buffer(buffer)),
+            false,
+        );
+        return Ok(RESP::Integer(length as i64));
+    };
+
+    if patch.is_empty() {
+        return Ok(RESP::Integer(value.len() as i64));
+    }
+    check_string_length(offset + patch.len())?;
+
+    let buffer = value.make_mut();
+    if buffer.len() < offset + patch.len() {
+        buffer.resize(offset + patch.len(), 0);
+    }
+    buffer[offset..offset + patch.len()].copy_from_slice(patch);
+    Ok(RESP::Integer(buffer.len() as i64))
+}
+
+fn strlen(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let length = lookup_string(ctx, &args[1])?.map_or(0, StringValue::len);
+    Ok(RESP::Integer(length as i64))
+}
+
+fn getdel(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let key = &args[1];
+    let Some(value) = lookup_string(ctx, key)?.map(StringValue::to_bytes) else {
+        return Ok(RESP::NullBulkString);
+    };
+    ctx.db.remove(key);
+    Ok(RESP::BulkString(value))
+}
+
+/// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+/// PXAT unix-time-milliseconds | PERSIST]
+fn getex(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let mut expire_at = None;
+    let mut persist = false;
+
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        let option = option.to_ascii_uppercase();
+        match option.as_slice() {
+            b"PERSIST" if expire_at.is_none() && !persist => persist = true,
+            b"EX" | b"PX" | b"EXAT" | b"PXAT" if expire_at.is_none() && !persist => {
+                expire_at = Some(parse_expire_option(&option, options.next(), "getex")?);
+            }
+            _ => return Err(CommandError::Syntax),
+        }
+    }
+
+    let key = &args[1];
+    let Some(value) = lookup_string(ctx, key)?.map(StringValue::to_bytes) else {
+        return Ok(RESP::NullBulkString);
+    };
+    if let Some(when) = expire_at {
+        ctx.db.set_expiry(key, when);
+    } else if persist {
+        ctx.db.persist(key);
+    }
+    Ok(RESP::BulkString(value))
+}
+
+/// MGET key [key ...]. Keys that are missing or hold another type come back
+/// as nulls.
+fn mget(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    Ok(RESP::Array(
+        args[1..]
+            .iter()
+            .map(|key| match ctx.db.get(key) {
+                Some(Value::String(value)) => RESP::BulkString(value.to_bytes()),
+                _ => RESP::NullBulkString,
+            })
+            .collect(),
+    ))
+}
+
+fn mset(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let pairs = key_value_pairs(args, "mset")?;
+    for pair in pairs {
+        let value = StringValue::from_bytes(pair[1].clone());
+        ctx.db.set(pair[0].clone(), Value::String(value), false);
+    }
+    Ok(RESP::SimpleString("OK".to_string()))
+}
+
+/// MSETNX key value [key value ...]: sets every key, or none of them if any
+/// already exists.
+fn msetnx(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let pairs = key_value_pairs(args, "msetnx")?;
+    if pairs.clone().any(|pair| ctx.db.contains(&pair[0])) {
+        return Ok(RESP::Integer(0));
+    }
+    for pair in pairs {
+        let value = StringValue::from_bytes(pair[1].clone());
+        ctx.db.set(pair[0].clone(), Value::String(value), false);
+    }
+    Ok(RESP::Integer(1))
+}
+
+fn key_value_pairs<'a>(
+    args: &'a [Bytes],
+    command: &str,
+) -> Result<std::slice::ChunksExact<'a, Bytes>, CommandError> {
+    let pairs = args[1..].chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err(CommandError::WrongArity(command.to_string()));
+    }
+    Ok(pairs)
+}
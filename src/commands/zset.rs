@@ -0,0 +1,1388 @@
+use std::{collections::HashMap, ops::Range, time::Instant};
+
+use bytes::Bytes;
+
+use super::{
+    format_f64, list::resolve_range, parse_f64, parse_i64, parse_timeout, random_sample, Block,
+    Command, CommandError, CommandFlags, CommandResult, Context, ScanArgs,
+};
+use crate::{
+    db::Value,
+    resp::RESP,
+    types::{Set, SortedSet},
+};
+
+const READONLY_FAST: CommandFlags = CommandFlags::READONLY.union(CommandFlags::FAST);
+const WRITE_FAST: CommandFlags = CommandFlags::WRITE.union(CommandFlags::FAST);
+const WRITE_BLOCKING: CommandFlags = CommandFlags::WRITE.union(CommandFlags::BLOCKING);
+const READONLY_MOVABLE_KEYS: CommandFlags =
+    CommandFlags::READONLY.union(CommandFlags::MOVABLE_KEYS);
+const WRITE_MOVABLE_KEYS: CommandFlags = CommandFlags::WRITE.union(CommandFlags::MOVABLE_KEYS);
+
+pub(super) const COMMANDS: &[Command] = &[
+    Command::new("zadd", zadd, -4, WRITE_FAST, 1, 1, 1),
+    Command::new("zincrby", zincrby, 4, WRITE_FAST, 1, 1, 1),
+    Command::new("zrem", zrem, -3, WRITE_FAST, 1, 1, 1),
+    Command::new("zcard", zcard, 2, READONLY_FAST, 1, 1, 1),
+    Command::new("zscore", zscore, 3, READONLY_FAST, 1, 1, 1),
+    Command::new("zmscore", zmscore, -3, READONLY_FAST, 1, 1, 1),
+    Command::new("zrank", zrank, -3, READONLY_FAST, 1, 1, 1),
+    Command::new("zrevrank", zrevrank, -3, READONLY_FAST, 1, 1, 1),
+    Command::new("zcount", zcount, 4, READONLY_FAST, 1, 1, 1),
+    Command::new("zrange", zrange, -4, CommandFlags::READONLY, 1, 1, 1),
+    Command::new("zrangestore", zrangestore, -5, CommandFlags::WRITE, 1, 2, 1),
+    Command::new("zrevrange", zrevrange, -4, CommandFlags::READONLY, 1, 1, 1),
+    Command::new(
+        "zrangebyscore",
+        zrangebyscore,
+        -4,
+        CommandFlags::READONLY,
+        1,
+        1,
+        1,
+    ),
+    Command::new(
+        "zrevrangebyscore",
+        zrevrangebyscore,
+        -4,
+        CommandFlags::READONLY,
+        1,
+        1,
+        1,
+    ),
+    Command::new(
+        "zrangebylex",
+        zrangebylex,
+        -4,
+        CommandFlags::READONLY,
+        1,
+        1,
+        1,
+    ),
+    Command::new(
+        "zrevrangebylex",
+        zrevrangebylex,
+        -4,
+        CommandFlags::READONLY,
+        1,
+        1,
+        1,
+    ),
+    Command::new("zlexcount", zlexcount, 4, READONLY_FAST, 1, 1, 1),
+    Command::new(
+        "zremrangebyrank",
+        zremrangebyrank,
+        4,
+        CommandFlags::WRITE,
+        1,
+        1,
+        1,
+    ),
+    Command::new(
+        "zremrangebyscore",
+        zremrangebyscore,
+        4,
+        CommandFlags::WRITE,
+        1,
+        1,
+        1,
+    ),
+    Command::new(
+        "zremrangebylex",
+        zremrangebylex,
+        4,
+        CommandFlags::WRITE,
+        1,
+        1,
+        1,
+    ),
+    Command::new("zunion", zunion, -3, READONLY_MOVABLE_KEYS, 0, 0, 0),
+    Command::new("zinter", zinter, -3, READONLY_MOVABLE_KEYS, 0, 0, 0),
+    Command::new("zdiff", zdiff, -3, READONLY_MOVABLE_KEYS, 0, 0, 0),
+    Command::new("zunionstore", zunionstore, -4, WRITE_MOVABLE_KEYS, 1, 1, 1),
+    Command::new("zinterstore", zinterstore, -4, WRITE_MOVABLE_KEYS, 1, 1, 1),
+    Command::new("zdiffstore", zdiffstore, -4, WRITE_MOVABLE_KEYS, 1, 1, 1),
+    Command::new("zpopmin", zpopmin, -2, WRITE_FAST, 1, 1, 1),
+    Command::new("zpopmax", zpopmax, -2, WRITE_FAST, 1, 1, 1),
+    Command::new("zmpop", zmpop, -4, WRITE_MOVABLE_KEYS, 0, 0, 0),
+    Command::new("bzpopmin", bzpopmin, -3, WRITE_BLOCKING, 1, -2, 1),
+    Command::new("bzpopmax", bzpopmax, -3, WRITE_BLOCKING, 1, -2, 1),
+    Command::new(
+        "bzmpop",
+        bzmpop,
+        -5,
+        WRITE_BLOCKING.union(CommandFlags::MOVABLE_KEYS),
+        0,
+        0,
+        0,
+    ),
+    Command::new(
+        "zrandmember",
+        zrandmember,
+        -2,
+        CommandFlags::READONLY,
+        1,
+        1,
+        1,
+    ),
+    Command::new("zscan", zscan, -3, CommandFlags::READONLY, 1, 1, 1),
+];
+
+/// The sorted set stored at `key`, if there is one.
+fn lookup_zset<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<&'a SortedSet>, CommandError> {
+    match ctx.db.get(key) {
+        Some(Value::SortedSet(zset)) => Ok(Some(zset)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn lookup_zset_mut<'a>(
+    ctx: &'a mut Context,
+    key: &[u8],
+) -> Result<Option<&'a mut SortedSet>, CommandError> {
+    match ctx.db.get_mut(key) {
+        Some(Value::SortedSet(zset)) => Ok(Some(zset)),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+fn new_zset(ctx: &Context) -> SortedSet {
+    SortedSet::new(
+        ctx.config.zset_max_listpack_entries,
+        ctx.config.zset_max_listpack_value,
+    )
+}
+
+/// The sorted set stored at `key`, created empty if the key is missing.
+fn lookup_zset_or_create<'a>(
+    ctx: &'a mut Context,
+    key: &Bytes,
+) -> Result<&'a mut SortedSet, CommandError> {
+    if !ctx.db.contains(key) {
+        let zset = new_zset(ctx);
+        ctx.db.set(key.clone(), Value::SortedSet(zset), false);
+    }
+    let Some(zset) = lookup_zset_mut(ctx, key)? else {
+        unreachable!("key was just created");
+    };
+    Ok(zset)
+}
+
+/// Deletes `key` if it holds a sorted set that has been emptied.
+fn remove_if_empty(ctx: &mut Context, key: &[u8]) {
+    if matches!(ctx.db.get(key), Some(Value::SortedSet(zset)) if zset.is_empty()) {
+        ctx.db.remove(key);
+    }
+}
+
+fn score_reply(score: f64) -> RESP {
+    RESP::BulkString(Bytes::from(format_f64(score)))
+}
+
+/// Replies with members, each followed by its score if `with_scores` is set.
+fn elements_reply<'a>(elements: impl Iterator<Item = (&'a [u8], f64)>, with_scores: bool) -> RESP {
+    let mut reply = Vec::new();
+    for (member, score) in elements {
+        reply.push(RESP::BulkString(Bytes::copy_from_slice(member)));
+        if with_scores {
+            reply.push(score_reply(score));
+        }
+    }
+    RESP::Array(reply)
+}
+
+/// ZADD key [NX | XX] [GT | LT] [CH] [INCR] score member [score member ...]
+///
+/// Replies with the number of members added, plus those whose score
+/// changed with CH. With INCR it acts like ZINCRBY and replies with the new
+/// score, or null if a condition prevented the update.
+fn zadd(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let (mut nx, mut xx, mut gt, mut lt, mut ch, mut incr) =
+        (false, false, false, false, false, false);
+    let mut i = 2;
+    while let Some(option) = args.get(i) {
+        match option.to_ascii_uppercase().as_slice() {
+            b"NX" => nx = true,
+            b"XX" => xx = true,
+            b"GT" => gt = true,
+            b"LT" => lt = true,
+            b"CH" => ch = true,
+            b"INCR" => incr = true,
+            _ => break,
+        }
+        i += 1;
+    }
+
+    let pairs = &args[i..];
+    if pairs.is_empty() || !pairs.chunks_exact(2).remainder().is_empty() {
+        return Err(CommandError::Syntax);
+    }
+    if nx && xx {
+        return Err(CommandError::Message(
+            "XX and NX options at the same time are not compatible",
+        ));
+    }
+    if (gt && lt) || (nx && (gt || lt)) {
+        return Err(CommandError::Message(
+            "GT, LT, and/or NX options at the same time are not compatible",
+        ));
+    }
+    if incr && pairs.len() > 2 {
+        return Err(CommandError::Message(
+            "INCR option supports a single increment-element pair",
+        ));
+    }
+    let scores = pairs
+        .iter()
+        .step_by(2)
+        .map(|score| parse_f64(score))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let key = &args[1];
+    if xx && lookup_zset(ctx, key)?.is_none() {
+        return Ok(if incr {
+            RESP::NullBulkString
+        } else {
+            RESP::Integer(0)
+        });
+    }
+
+    let zset = lookup_zset_or_create(ctx, key)?;
+    let (mut added, mut updated) = (0, 0);
+    let mut result = None;
+    for (pair, &score) in pairs.chunks_exact(2).zip(&scores) {
+        let member = &pair[1];
+        match zset.score(member) {
+            Some(_) if nx => {}
+            None if xx => {}
+            Some(current) => {
+                let score = if incr { current + score } else { score };
+                if score.is_nan() {
+                    return Err(CommandError::Message(
+                        "resulting score is not a number (NaN)",
+                    ));
+                }
+                if (gt && score <= current) || (lt && score >= current) {
+                    continue;
+                }
+                if score != current {
+                    zset.insert(member, score);
+                    updated += 1;
+                }
+                result = Some(score);
+            }
+            None => {
+                zset.insert(member, score);
+                added += 1;
+                result = Some(score);
+            }
+        }
+    }
+
+    Ok(if incr {
+        result.map_or(RESP::NullBulkString, score_reply)
+    } else if ch {
+        RESP::Integer(added + updated)
+    } else {
+        RESP::Integer(added)
+    })
+}
+
+fn zincrby(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let increment = parse_f64(&args[2])?;
+    let member = &args[3];
+    let zset = lookup_zset_or_create(ctx, &args[1])?;
+    let score = zset.score(member).unwrap_or(0.0) + increment;
+    if score.is_nan() {
+        return Err(CommandError::Message(
+            "resulting score is not a number (NaN)",
+        ));
+    }
+    zset.insert(member, score);
+    Ok(score_reply(score))
+}
+
+fn zrem(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let Some(zset) = lookup_zset_mut(ctx, &args[1])? else {
+        return Ok(RESP::Integer(0));
+    };
+    let removed = args[2..]
+        .iter()
+        .filter(|member| zset.remove(member))
+        .count();
+    remove_if_empty(ctx, &args[1]);
+    Ok(RESP::Integer(removed as i64))
+}
+
+fn zcard(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let len = lookup_zset(ctx, &args[1])?.map_or(0, SortedSet::len);
+    Ok(RESP::Integer(len as i64))
+}
+
+fn zscore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let score = lookup_zset(ctx, &args[1])?.and_then(|zset| zset.score(&args[2]));
+    Ok(score.map_or(RESP::NullBulkString, score_reply))
+}
+
+fn zmscore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let zset = lookup_zset(ctx, &args[1])?;
+    let scores = args[2..]
+        .iter()
+        .map(|member| {
+            zset.and_then(|zset| zset.score(member))
+                .map_or(RESP::NullBulkString, score_reply)
+        })
+        .collect();
+    Ok(RESP::Array(scores))
+}
+
+fn zrank(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    rank_generic(ctx, args, false)
+}
+
+fn zrevrank(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    rank_generic(ctx, args, true)
+}
+
+/// ZRANK/ZREVRANK key member [WITHSCORE]
+fn rank_generic(ctx: &mut Context, args: &[Bytes], rev: bool) -> CommandResult {
+    let with_score = match args {
+        [_, _, _] => false,
+        [_, _, _, option] if option.eq_ignore_ascii_case(b"WITHSCORE") => true,
+        _ => return Err(CommandError::Syntax),
+    };
+    let member = &args[2];
+    let found = lookup_zset(ctx, &args[1])?.and_then(|zset| {
+        let rank = zset.rank(member)?;
+        let rank = if rev { zset.len() - 1 - rank } else { rank };
+        Some((rank, zset.score(member)?))
+    });
+
+    Ok(match found {
+        Some((rank, score)) if with_score => {
+            RESP::Array(vec![RESP::Integer(rank as i64), score_reply(score)])
+        }
+        Some((rank, _)) => RESP::Integer(rank as i64),
+        None if with_score => RESP::NullArray,
+        None => RESP::NullBulkString,
+    })
+}
+
+fn zcount(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let by = RangeBy::Score(ScoreBound::parse(&args[2])?, ScoreBound::parse(&args[3])?);
+    let count = lookup_zset(ctx, &args[1])?.map_or(0, |zset| by.ranks(zset, false).len());
+    Ok(RESP::Integer(count as i64))
+}
+
+/// One end of a score range: a score that is included unless prefixed with
+/// `(`, with `-inf` and `+inf` standing for the extremes.
+#[derive(Clone, Copy)]
+struct ScoreBound {
+    score: f64,
+    exclusive: bool,
+}
+
+impl ScoreBound {
+    fn parse(arg: &[u8]) -> Result<ScoreBound, CommandError> {
+        let (arg, exclusive) = match arg.strip_prefix(b"(") {
+            Some(rest) => (rest, true),
+            None => (arg, false),
+        };
+        let score =
+            parse_f64(arg).map_err(|_| CommandError::Message("min or max is not a float"))?;
+        Ok(ScoreBound { score, exclusive })
+    }
+
+    /// Whether `score` falls short of this bound taken as a minimum.
+    fn is_below(&self, score: f64) -> bool {
+        if self.exclusive {
+            score <= self.score
+        } else {
+            score < self.score
+        }
+    }
+
+    /// Whether `score` goes past this bound taken as a maximum.
+    fn is_above(&self, score: f64) -> bool {
+        if self.exclusive {
+            score >= self.score
+        } else {
+            score > self.score
+        }
+    }
+}
+
+/// One end of a lex range: `[member` or `(member` for an inclusive or
+/// exclusive bound, with `-` and `+` standing for the extremes.
+enum LexBound {
+    Min,
+    Max,
+    Inclusive(Bytes),
+    Exclusive(Bytes),
+}
+
+impl LexBound {
+    fn parse(arg: &Bytes) -> Result<LexBound, CommandError> {
+        match arg.first() {
+            Some(b'-') if arg.len() == 1 => Ok(LexBound::Min),
+            Some(b'+') if arg.len() == 1 => Ok(LexBound::Max),
+            Some(b'[') => Ok(LexBound::Inclusive(arg.slice(1..))),
+            Some(b'(') => Ok(LexBound::Exclusive(arg.slice(1..))),
+            _ => Err(CommandError::Message(
+                "min or max not valid string range item",
+            )),
+        }
+    }
+
+    fn is_below(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(bound) => member < &bound[..],
+            LexBound::Exclusive(bound) => member <= &bound[..],
+        }
+    }
+
+    fn is_above(&self, member: &[u8]) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(bound) => member > &bound[..],
+            LexBound::Exclusive(bound) => member >= &bound[..],
+        }
+    }
+}
+
+/// What a range command selects elements by.
+enum RangeBy {
+    /// Start and stop ranks, which may be negative to count from the end.
+    Rank(i64, i64),
+    Score(ScoreBound, ScoreBound),
+    /// Assumes every element has the same score, as in Redis.
+    Lex(LexBound, LexBound),
+}
+
+impl RangeBy {
+    /// The ranks, in ascending order, of the elements in the range. Rank
+    /// ranges count from the highest score if `rev` is set.
+    fn ranks(&self, zset: &SortedSet, rev: bool) -> Range<usize> {
+        let len = zset.len();
+        let (start, end) = match self {
+            RangeBy::Rank(start, stop) => match resolve_range(*start, *stop, len) {
+                Some((start, stop)) if rev => (len - 1 - stop, len - start),
+                Some((start, stop)) => (start, stop + 1),
+                None => (0, 0),
+            },
+            RangeBy::Score(min, max) => (
+                zset.partition_point(|score, _| min.is_below(score)),
+                zset.partition_point(|score, _| !max.is_above(score)),
+            ),
+            RangeBy::Lex(min, max) => (
+                zset.partition_point(|_, member| min.is_below(member)),
+                zset.partition_point(|_, member| !max.is_above(member)),
+            ),
+        };
+        start..end.max(start)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum RangeKind {
+    Rank,
+    Score,
+    Lex,
+}
+
+/// The parsed arguments of ZRANGE and the commands it subsumes.
+struct RangeArgs {
+    by: RangeBy,
+    rev: bool,
+    /// LIMIT offset and count, where a negative count means no limit.
+    limit: Option<(i64, i64)>,
+    with_scores: bool,
+}
+
+impl RangeArgs {
+    /// Parses `start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count]
+    /// [WITHSCORES]`. Commands that imply a kind of range or a direction
+    /// pass it in, which rules out the option for it. WITHSCORES is not
+    /// accepted when storing the result.
+    fn parse(
+        args: &[Bytes],
+        mut kind: Option<RangeKind>,
+        mut rev: Option<bool>,
+        store: bool,
+    ) -> Result<RangeArgs, CommandError> {
+        let mut limit = None;
+        let mut with_scores = false;
+
+        let mut options = args[2..].iter();
+        while let Some(option) = options.next() {
+            match option.to_ascii_uppercase().as_slice() {
+                b"WITHSCORES" if !store => with_scores = true,
+                b"LIMIT" => {
+                    let (Some(offset), Some(count)) = (options.next(), options.next()) else {
+                        return Err(CommandError::Syntax);
+                    };
+                    limit = Some((parse_i64(offset)?, parse_i64(count)?));
+                }
+                b"BYSCORE" if kind.is_none() => kind = Some(RangeKind::Score),
+                b"BYLEX" if kind.is_none() => kind = Some(RangeKind::Lex),
+                b"REV" if rev.is_none() => rev = Some(true),
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+
+        let kind = kind.unwrap_or(RangeKind::Rank);
+        let rev = rev.unwrap_or(false);
+        if limit.is_some() && kind == RangeKind::Rank {
+            return Err(CommandError::Message(
+                "syntax error, LIMIT is only supported in combination with either BYSCORE or BYLEX",
+            ));
+        }
+        if with_scores && kind == RangeKind::Lex {
+            return Err(CommandError::Message(
+                "syntax error, WITHSCORES not supported in combination with BYLEX",
+            ));
+        }
+
+        // Score and lex ranges are given from max to min when reversed.
+        let (min, max) = if rev {
+            (&args[1], &args[0])
+        } else {
+            (&args[0], &args[1])
+        };
+        let by = match kind {
+            RangeKind::Rank => RangeBy::Rank(parse_i64(&args[0])?, parse_i64(&args[1])?),
+            RangeKind::Score => RangeBy::Score(ScoreBound::parse(min)?, ScoreBound::parse(max)?),
+            RangeKind::Lex => RangeBy::Lex(LexBound::parse(min)?, LexBound::parse(max)?),
+        };
+
+        Ok(RangeArgs {
+            by,
+            rev,
+            limit,
+            with_scores,
+        })
+    }
+
+    /// The selected elements, in the order they are replied with.
+    fn select<'a>(&self, zset: &'a SortedSet) -> Box<dyn Iterator<Item = (&'a [u8], f64)> + 'a> {
+        let ranks = self.by.ranks(zset, self.rev);
+        let (offset, count) = self.limit.unwrap_or((0, -1));
+        if offset < 0 {
+            return Box::new(std::iter::empty());
+        }
+        let skip = (offset as usize).min(ranks.len());
+        let take = match count {
+            count if count < 0 => ranks.len() - skip,
+            count => (count as usize).min(ranks.len() - skip),
+        };
+        if take == 0 {
+            return Box::new(std::iter::empty());
+        }
+
+        let elements = if self.rev {
+            zset.iter_from(ranks.end - 1 - skip, true)
+        } else {
+            zset.iter_from(ranks.start + skip, false)
+        };
+        Box::new(elements.take(take))
+    }
+}
+
+/// Replies with the elements of the sorted set at `key` that `range`
+/// selects.
+fn range_generic(ctx: &mut Context, key: &[u8], range: RangeArgs) -> CommandResult {
+    let Some(zset) = lookup_zset(ctx, key)? else {
+        return Ok(RESP::Array(vec![]));
+    };
+    Ok(elements_reply(range.select(zset), range.with_scores))
+}
+
+/// ZRANGE key start stop [BYSCORE | BYLEX] [REV] [LIMIT offset count]
+/// [WITHSCORES]
+fn zrange(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = RangeArgs::parse(&args[2..], None, None, false)?;
+    range_generic(ctx, &args[1], range)
+}
+
+fn zrevrange(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = RangeArgs::parse(&args[2..], Some(RangeKind::Rank), Some(true), false)?;
+    range_generic(ctx, &args[1], range)
+}
+
+fn zrangebyscore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = RangeArgs::parse(&args[2..], Some(RangeKind::Score), Some(false), false)?;
+    range_generic(ctx, &args[1], range)
+}
+
+fn zrevrangebyscore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = RangeArgs::parse(&args[2..], Some(RangeKind::Score), Some(true), false)?;
+    range_generic(ctx, &args[1], range)
+}
+
+fn zrangebylex(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = RangeArgs::parse(&args[2..], Some(RangeKind::Lex), Some(false), false)?;
+    range_generic(ctx, &args[1], range)
+}
+
+fn zrevrangebylex(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = RangeArgs::parse(&args[2..], Some(RangeKind::Lex), Some(true), false)?;
+    range_generic(ctx, &args[1], range)
+}
+
+/// ZRANGESTORE destination source start stop [BYSCORE | BYLEX] [REV]
+/// [LIMIT offset count], replacing the destination with the selected
+/// elements, or deleting it if there are none.
+fn zrangestore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let range = RangeArgs::parse(&args[3..], None, None, true)?;
+    let mut result = new_zset(ctx);
+    if let Some(zset) = lookup_zset(ctx, &args[2])? {
+        for (member, score) in range.select(zset) {
+            result.insert(member, score);
+        }
+    }
+
+    let destination = &args[1];
+    let len = result.len();
+    if len == 0 {
+        ctx.db.remove(destination);
+    } else {
+        ctx.db
+            .set(destination.clone(), Value::SortedSet(result), false);
+    }
+    Ok(RESP::Integer(len as i64))
+}
+
+fn zlexcount(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let by = RangeBy::Lex(LexBound::parse(&args[2])?, LexBound::parse(&args[3])?);
+    let count = lookup_zset(ctx, &args[1])?.map_or(0, |zset| by.ranks(zset, false).len());
+    Ok(RESP::Integer(count as i64))
+}
+
+/// Removes the elements of the sorted set at `key` that `by` selects,
+/// replying with how many there were.
+fn remove_range_generic(ctx: &mut Context, key: &[u8], by: RangeBy) -> CommandResult {
+    let Some(zset) = lookup_zset_mut(ctx, key)? else {
+        return Ok(RESP::Integer(0));
+    };
+    let ranks = by.ranks(zset, false);
+    let removed = zset.remove_range(ranks);
+    remove_if_empty(ctx, key);
+    Ok(RESP::Integer(removed as i64))
+}
+
+fn zremrangebyrank(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let by = RangeBy::Rank(parse_i64(&args[2])?, parse_i64(&args[3])?);
+    remove_range_generic(ctx, &args[1], by)
+}
+
+fn zremrangebyscore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let by = RangeBy::Score(ScoreBound::parse(&args[2])?, ScoreBound::parse(&args[3])?);
+    remove_range_generic(ctx, &args[1], by)
+}
+
+fn zremrangebylex(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let by = RangeBy::Lex(LexBound::parse(&args[2])?, LexBound::parse(&args[3])?);
+    remove_range_generic(ctx, &args[1], by)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Union,
+    Inter,
+    Diff,
+}
+
+/// How the scores a member has in several inputs are combined.
+#[derive(Clone, Copy)]
+enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn apply(self, a: f64, b: f64) -> f64 {
+        match self {
+            // Opposite infinities cancel out to 0, as in Redis.
+            Aggregate::Sum => zero_if_nan(a + b),
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+fn zero_if_nan(score: f64) -> f64 {
+    if score.is_nan() {
+        0.0
+    } else {
+        score
+    }
+}
+
+/// An input of the aggregation commands, which take plain sets as well,
+/// with every member scoring 1.
+enum Source<'a> {
+    Set(&'a Set),
+    SortedSet(&'a SortedSet),
+}
+
+impl Source<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Source::Set(set) => set.len(),
+            Source::SortedSet(zset) => zset.len(),
+        }
+    }
+
+    fn score(&self, member: &[u8]) -> Option<f64> {
+        match self {
+            Source::Set(set) => set.contains(member).then_some(1.0),
+            Source::SortedSet(zset) => zset.score(member),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (Bytes, f64)> + '_> {
+        match self {
+            Source::Set(set) => Box::new(set.iter().map(|member| (member, 1.0))),
+            Source::SortedSet(zset) => Box::new(
+                zset.iter()
+                    .map(|(member, score)| (Bytes::copy_from_slice(member), score)),
+            ),
+        }
+    }
+}
+
+fn lookup_source<'a>(ctx: &'a mut Context, key: &[u8]) -> Result<Option<Source<'a>>, CommandError> {
+    match ctx.db.get(key) {
+        Some(Value::Set(set)) => Ok(Some(Source::Set(set))),
+        Some(Value::SortedSet(zset)) => Ok(Some(Source::SortedSet(zset))),
+        Some(_) => Err(CommandError::WrongType),
+        None => Ok(None),
+    }
+}
+
+/// The parsed `numkeys key [key ...] [WEIGHTS weight [weight ...]]
+/// [AGGREGATE SUM | MIN | MAX] [WITHSCORES]` arguments of the aggregation
+/// commands. The difference takes neither weights nor an aggregate, and
+/// the storing variants do not reply with scores.
+struct Combine<'a> {
+    keys: &'a [Bytes],
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    with_scores: bool,
+}
+
+impl Combine<'_> {
+    fn parse<'a>(
+        args: &'a [Bytes],
+        operation: Operation,
+        store: bool,
+        command: &'static str,
+    ) -> Result<Combine<'a>, CommandError> {
+        let numkeys = parse_i64(&args[0])?;
+        if numkeys < 1 {
+            return Err(CommandError::NoInputKeys(command));
+        }
+        let numkeys = numkeys as usize;
+        if numkeys > args.len() - 1 {
+            return Err(CommandError::Syntax);
+        }
+
+        let mut weights = vec![1.0; numkeys];
+        let mut aggregate = Aggregate::Sum;
+        let mut with_scores = false;
+        let mut options = args[numkeys + 1..].iter();
+        while let Some(option) = options.next() {
+            match option.to_ascii_uppercase().as_slice() {
+                b"WEIGHTS" if operation != Operation::Diff => {
+                    for weight in &mut weights {
+                        let Some(arg) = options.next() else {
+                            return Err(CommandError::Syntax);
+                        };
+                        *weight = parse_f64(arg)
+                            .map_err(|_| CommandError::Message("weight value is not a float"))?;
+                    }
+                }
+                b"AGGREGATE" if operation != Operation::Diff => {
+                    let name = options.next().map(|name| name.to_ascii_uppercase());
+                    aggregate = match name.as_deref() {
+                        Some(b"SUM") => Aggregate::Sum,
+                        Some(b"MIN") => Aggregate::Min,
+                        Some(b"MAX") => Aggregate::Max,
+                        _ => return Err(CommandError::Syntax),
+                    };
+                }
+                b"WITHSCORES" if !store => with_scores = true,
+                _ => return Err(CommandError::Syntax),
+            }
+        }
+
+        Ok(Combine {
+            keys: &args[1..=numkeys],
+            weights,
+            aggregate,
+            with_scores,
+        })
+    }
+
+    /// Computes the union, intersection or difference of the inputs, with
+    /// missing keys counting as empty. Every key is type checked before
+    /// anything is computed.
+    fn run(&self, ctx: &mut Context, operation: Operation) -> Result<SortedSet, CommandError> {
+        let mut lens = Vec::with_capacity(self.keys.len());
+        for key in self.keys {
+            lens.push(lookup_source(ctx, key)?.map(|source| source.len()));
+        }
+
+        let mut scores: HashMap<Bytes, f64> = HashMap::new();
+        match operation {
+            Operation::Union => {
+                for (key, &weight) in self.keys.iter().zip(&self.weights) {
+                    let Some(source) = lookup_source(ctx, key)? else {
+                        continue;
+                    };
+                    for (member, score) in source.iter() {
+                        let score = zero_if_nan(score * weight);
+                        scores
+                            .entry(member)
+                            .and_modify(|total| *total = self.aggregate.apply(*total, score))
+                            .or_insert(score);
+                    }
+                }
+            }
+            Operation::Inter if lens.contains(&None) => {}
+            Operation::Inter => {
+                // Start from the smallest input, as the result is no bigger.
+                let smallest = (0..self.keys.len()).min_by_key(|&i| lens[i]).unwrap_or(0);
+                let members: Vec<Bytes> = match lookup_source(ctx, &self.keys[smallest])? {
+                    Some(source) => source.iter().map(|(member, _)| member).collect(),
+                    None => Vec::new(),
+                };
+                'members: for member in members {
+                    let mut total = None;
+                    for (key, &weight) in self.keys.iter().zip(&self.weights) {
+                        let source = lookup_source(ctx, key)?;
+                        let Some(score) = source.and_then(|source| source.score(&member)) else {
+                            continue 'members;
+                        };
+                        let score = zero_if_nan(score * weight);
+                        total = Some(match total {
+                            Some(total) => self.aggregate.apply(total, score),
+                            None => score,
+                        });
+                    }
+                    if let Some(total) = total {
+                        scores.insert(member, total);
+                    }
+                }
+            }
+            Operation::Diff => {
+                if let Some(source) = lookup_source(ctx, &self.keys[0])? {
+                    scores.extend(source.iter());
+                }
+                for key in &self.keys[1..] {
+                    if scores.is_empty() {
+                        break;
+                    }
+                    if let Some(source) = lookup_source(ctx, key)? {
+                        scores.retain(|member, _| source.score(member).is_none());
+                    }
+                }
+            }
+        }
+
+        let mut result = new_zset(ctx);
+        for (member, score) in scores {
+            result.insert(&member, score);
+        }
+        Ok(result)
+    }
+}
+
+/// ZUNION/ZINTER/ZDIFF numkeys key [key ...] ..., replying with the result
+/// in order.
+fn combine_generic(
+    ctx: &mut Context,
+    args: &[Bytes],
+    operation: Operation,
+    command: &'static str,
+) -> CommandResult {
+    let combine = Combine::parse(&args[1..], operation, false, command)?;
+    let result = combine.run(ctx, operation)?;
+    Ok(elements_reply(result.iter(), combine.with_scores))
+}
+
+fn zunion(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    combine_generic(ctx, args, Operation::Union, "zunion")
+}
+
+fn zinter(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    combine_generic(ctx, args, Operation::Inter, "zinter")
+}
+
+fn zdiff(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    combine_generic(ctx, args, Operation::Diff, "zdiff")
+}
+
+/// ZUNIONSTORE/ZINTERSTORE/ZDIFFSTORE destination numkeys key [key ...]
+/// ..., replacing the destination with the result, or deleting it if the
+/// result is empty.
+fn store(
+    ctx: &mut Context,
+    args: &[Bytes],
+    operation: Operation,
+    command: &'static str,
+) -> CommandResult {
+    let combine = Combine::parse(&args[2..], operation, true, command)?;
+    let result = combine.run(ctx, operation)?;
+    let destination = &args[1];
+    let len = result.len();
+    if len == 0 {
+        ctx.db.remove(destination);
+    } else {
+        ctx.db
+            .set(destination.clone(), Value::SortedSet(result), false);
+    }
+    Ok(RESP::Integer(len as i64))
+}
+
+fn zunionstore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    store(ctx, args, Operation::Union, "zunionstore")
+}
+
+fn zinterstore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    store(ctx, args, Operation::Inter, "zinterstore")
+}
+
+fn zdiffstore(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    store(ctx, args, Operation::Diff, "zdiffstore")
+}
+
+/// Removes up to `count` elements with the lowest scores, or the highest
+/// if `max` is set, returning them in the order they were popped.
+fn pop_many(zset: &mut SortedSet, max: bool, count: usize) -> Vec<(Bytes, f64)> {
+    let len = zset.len();
+    let count = count.min(len);
+    if count == 0 {
+        return Vec::new();
+    }
+    let (ranks, start) = if max {
+        (len - count..len, len - 1)
+    } else {
+        (0..count, 0)
+    };
+    let popped = zset
+        .iter_from(start, max)
+        .take(count)
+        .map(|(member, score)| (Bytes::copy_from_slice(member), score))
+        .collect();
+    zset.remove_range(ranks);
+    popped
+}
+
+/// ZPOPMIN/ZPOPMAX key [count], replying with the popped members, each
+/// followed by its score.
+fn pop_generic(ctx: &mut Context, args: &[Bytes], max: bool) -> CommandResult {
+    let count = match args {
+        [_, _] => 1,
+        [_, _, count] => match parse_i64(count) {
+            Ok(count) if count >= 0 => count as usize,
+            _ => {
+                return Err(CommandError::Message(
+                    "value is out of range, must be positive",
+                ))
+            }
+        },
+        _ => return Err(CommandError::Syntax),
+    };
+
+    let key = &args[1];
+    let Some(zset) = lookup_zset_mut(ctx, key)? else {
+        return Ok(RESP::Array(vec![]));
+    };
+    let popped = pop_many(zset, max, count);
+    remove_if_empty(ctx, key);
+    Ok(elements_reply(
+        popped.iter().map(|(member, score)| (&member[..], *score)),
+        true,
+    ))
+}
+
+fn zpopmin(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    pop_generic(ctx, args, false)
+}
+
+fn zpopmax(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    pop_generic(ctx, args, true)
+}
+
+/// Parses a MIN or MAX argument, returning whether it means the highest
+/// scores.
+fn parse_end(arg: &[u8]) -> Result<bool, CommandError> {
+    match arg.to_ascii_uppercase().as_slice() {
+        b"MIN" => Ok(false),
+        b"MAX" => Ok(true),
+        _ => Err(CommandError::Syntax),
+    }
+}
+
+/// The parsed `numkeys key [key ...] MIN | MAX [COUNT count]` arguments of
+/// ZMPOP and BZMPOP.
+struct MultiPop<'a> {
+    keys: &'a [Bytes],
+    max: bool,
+    count: usize,
+}
+
+impl MultiPop<'_> {
+    fn parse(args: &[Bytes]) -> Result<MultiPop<'_>, CommandError> {
+        let numkeys = match parse_i64(&args[0]) {
+            Ok(numkeys) if numkeys > 0 => numkeys as usize,
+            _ => return Err(CommandError::Message("numkeys should be greater than 0")),
+        };
+        let Some(end) = args.get(numkeys + 1) else {
+            return Err(CommandError::Syntax);
+        };
+        let max = parse_end(end)?;
+
+        let count = match &args[numkeys + 2..] {
+            [] => 1,
+            [option, count] if option.eq_ignore_ascii_case(b"COUNT") => match parse_i64(count) {
+                Ok(count) if count > 0 => count as usize,
+                _ => return Err(CommandError::Message("count should be greater than 0")),
+            },
+            _ => return Err(CommandError::Syntax),
+        };
+
+        Ok(MultiPop {
+            keys: &args[1..=numkeys],
+            max,
+            count,
+        })
+    }
+
+    /// Pops from the first key holding a sorted set, replying with its name
+    /// and the popped member and score pairs, or returns `None` if all the
+    /// keys are missing.
+    fn pop(&self, ctx: &mut Context) -> Result<Option<RESP>, CommandError> {
+        for key in self.keys {
+            let Some(zset) = lookup_zset_mut(ctx, key)? else {
+                continue;
+            };
+            let popped = pop_many(zset, self.max, self.count);
+            remove_if_empty(ctx, key);
+            let elements = popped
+                .into_iter()
+                .map(|(member, score)| {
+                    RESP::Array(vec![RESP::BulkString(member), score_reply(score)])
+                })
+                .collect();
+            return Ok(Some(RESP::Array(vec![
+                RESP::BulkString(key.clone()),
+                RESP::Array(elements),
+            ])));
+        }
+        Ok(None)
+    }
+}
+
+/// ZMPOP numkeys key [key ...] MIN | MAX [COUNT count]
+fn zmpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let pop = MultiPop::parse(&args[1..])?;
+    Ok(pop.pop(ctx)?.unwrap_or(RESP::NullArray))
+}
+
+/// Parks the client on `keys` until one of them holds a sorted set or the
+/// deadline passes, at which point it gets a null reply.
+fn block_on_zsets(ctx: &mut Context, keys: &[Bytes], deadline: Option<Instant>) -> CommandResult {
+    ctx.block = Some(Block {
+        keys: keys.to_vec(),
+        value_type: "zset",
+        deadline,
+    });
+    Ok(RESP::NullArray)
+}
+
+/// BZPOPMIN/BZPOPMAX key [key ...] timeout. Pops from the first of the keys
+/// that holds a sorted set, replying with the key, the member and its
+/// score, or blocks until one of them does.
+fn blocking_pop(ctx: &mut Context, args: &[Bytes], max: bool) -> CommandResult {
+    let deadline = parse_timeout(&args[args.len() - 1])?;
+    let keys = &args[1..args.len() - 1];
+
+    for key in keys {
+        let Some(zset) = lookup_zset_mut(ctx, key)? else {
+            continue;
+        };
+        let Some((member, score)) = pop_many(zset, max, 1).pop() else {
+            continue;
+        };
+        remove_if_empty(ctx, key);
+        return Ok(RESP::Array(vec![
+            RESP::BulkString(key.clone()),
+            RESP::BulkString(member),
+            score_reply(score),
+        ]));
+    }
+    block_on_zsets(ctx, keys, deadline)
+}
+
+fn bzpopmin(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    blocking_pop(ctx, args, false)
+}
+
+fn bzpopmax(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    blocking_pop(ctx, args, true)
+}
+
+/// BZMPOP timeout numkeys key [key ...] MIN | MAX [COUNT count]
+fn bzmpop(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let deadline = parse_timeout(&args[1])?;
+    let pop = MultiPop::parse(&args[2..])?;
+    match pop.pop(ctx)? {
+        Some(reply) => Ok(reply),
+        None => block_on_zsets(ctx, pop.keys, deadline),
+    }
+}
+
+/// ZRANDMEMBER key [count [WITHSCORES]], sampled by `random_sample`.
+fn zrandmember(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let (count, with_scores) = match args {
+        [_, _] => (None, false),
+        [_, _, count] => (Some(parse_i64(count)?), false),
+        [_, _, count, option] if option.eq_ignore_ascii_case(b"WITHSCORES") => {
+            (Some(parse_i64(count)?), true)
+        }
+        _ => return Err(CommandError::Syntax),
+    };
+    if count.is_some_and(|count| count.unsigned_abs() > i64::MAX as u64 / 2) {
+        return Err(CommandError::Message("value is out of range"));
+    }
+
+    let zset = lookup_zset(ctx, &args[1])?;
+    let Some(count) = count else {
+        let member = zset
+            .and_then(SortedSet::random_entry)
+            .map(|(member, _)| member);
+        return Ok(member.map_or(RESP::NullBulkString, |member| {
+            RESP::BulkString(Bytes::copy_from_slice(member))
+        }));
+    };
+    let Some(zset) = zset else {
+        return Ok(RESP::Array(vec![]));
+    };
+
+    let elements = random_sample(
+        count,
+        zset.len(),
+        zset.iter(),
+        || zset.random_entry(),
+        |&(member, _)| member,
+    );
+    Ok(elements_reply(elements.into_iter(), with_scores))
+}
+
+/// ZSCAN key cursor [MATCH pattern] [COUNT count]
+fn zscan(ctx: &mut Context, args: &[Bytes]) -> CommandResult {
+    let scan = ScanArgs::parse(&args[2..], false)?;
+    let mut items = Vec::new();
+    let cursor = match lookup_zset(ctx, &args[1])? {
+        Some(zset) => zset.scan(scan.cursor, scan.count, |member, score| {
+            if scan.matches(member) {
+                items.push(RESP::BulkString(Bytes::copy_from_slice(member)));
+                items.push(score_reply(score));
+            }
+        }),
+        None => 0,
+    };
+    Ok(RESP::Array(vec![
+        RESP::BulkString(Bytes::from(cursor.to_string())),
+        RESP::Array(items),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        commands::{tests::call, CommandTable},
+        config::Config,
+        db::Db,
+    };
+
+    /// Parses `args` as the arguments after the command name and runs the
+    /// operation, returning the result in order.
+    fn combine(db: &mut Db, operation: Operation, args: &[&str]) -> Vec<(String, f64)> {
+        let commands = CommandTable::new();
+        let config = Config::default();
+        let mut ctx = Context {
+            commands: &commands,
+            config: &config,
+            db,
+            block: None,
+        };
+        let args: Vec<Bytes> = args
+            .iter()
+            .map(|arg| Bytes::copy_from_slice(arg.as_bytes()))
+            .collect();
+        let combine = Combine::parse(&args, operation, false, "test").expect("arguments parse");
+        let result = combine
+            .run(&mut ctx, operation)
+            .expect("keys have the right type");
+        result
+            .iter()
+            .map(|(member, score)| (String::from_utf8_lossy(member).into_owned(), score))
+            .collect()
+    }
+
+    fn scores(expected: &[(&str, f64)]) -> Vec<(String, f64)> {
+        expected
+            .iter()
+            .map(|&(member, score)| (member.to_string(), score))
+            .collect()
+    }
+
+    /// `a` is {x: 1, y: 2} and `b` is {y: 3, z: 4}.
+    fn db_with_inputs() -> Db {
+        let mut db = Db::default();
+        call(&mut db, &["ZADD", "a", "1", "x", "2", "y"]);
+        call(&mut db, &["ZADD", "b", "3", "y", "4", "z"]);
+        db
+    }
+
+    #[test]
+    fn weights_scale_each_input() {
+        let mut db = db_with_inputs();
+        assert_eq!(
+            combine(
+                &mut db,
+                Operation::Union,
+                &["2", "a", "b", "WEIGHTS", "2", "3"]
+            ),
+            scores(&[("x", 2.0), ("z", 12.0), ("y", 13.0)])
+        );
+        assert_eq!(
+            combine(
+                &mut db,
+                Operation::Inter,
+                &["2", "a", "b", "WEIGHTS", "-1", "0.5"]
+            ),
+            scores(&[("y", -0.5)])
+        );
+    }
+
+    #[test]
+    fn aggregates_combine_the_scores_of_a_member() {
+        let mut db = db_with_inputs();
+        for (aggregate, union, inter) in [
+            ("SUM", [("x", 1.0), ("z", 4.0), ("y", 5.0)], 5.0),
+            ("MIN", [("x", 1.0), ("y", 2.0), ("z", 4.0)], 2.0),
+            ("MAX", [("x", 1.0), ("y", 3.0), ("z", 4.0)], 3.0),
+        ] {
+            assert_eq!(
+                combine(
+                    &mut db,
+                    Operation::Union,
+                    &["2", "a", "b", "AGGREGATE", aggregate]
+                ),
+                scores(&union)
+            );
+            assert_eq!(
+                combine(
+                    &mut db,
+                    Operation::Inter,
+                    &["2", "a", "b", "aggregate", aggregate]
+                ),
+                scores(&[("y", inter)])
+            );
+        }
+        assert_eq!(
+            combine(
+                &mut db,
+                Operation::Union,
+                &["2", "a", "b", "WEIGHTS", "1", "-1", "AGGREGATE", "MAX"]
+            ),
+            scores(&[("z", -4.0), ("x", 1.0), ("y", 2.0)])
+        );
+    }
+
+    #[test]
+    fn plain_set_members_score_one() {
+        let mut db = db_with_inputs();
+        call(&mut db, &["SADD", "s", "y", "w"]);
+        assert_eq!(
+            combine(&mut db, Operation::Union, &["2", "a", "s"]),
+            scores(&[("w", 1.0), ("x", 1.0), ("y", 3.0)])
+        );
+        assert_eq!(
+            combine(
+                &mut db,
+                Operation::Inter,
+                &["2", "s", "b", "WEIGHTS", "5", "1"]
+            ),
+            scores(&[("y", 8.0)])
+        );
+        assert_eq!(
+            combine(&mut db, Operation::Diff, &["2", "s", "a"]),
+            scores(&[("w", 1.0)])
+        );
+    }
+
+    #[test]
+    fn infinity_times_a_zero_weight_scores_zero() {
+        let mut db = Db::default();
+        call(&mut db, &["ZADD", "high", "+inf", "x", "1", "y"]);
+        call(&mut db, &["ZADD", "low", "-inf", "x"]);
+        assert_eq!(
+            combine(&mut db, Operation::Union, &["1", "high", "WEIGHTS", "0"]),
+            scores(&[("x", 0.0), ("y", 0.0)])
+        );
+        assert_eq!(
+            combine(
+                &mut db,
+                Operation::Inter,
+                &["2", "high", "low", "WEIGHTS", "0", "1"]
+            ),
+            scores(&[("x", f64::NEG_INFINITY)])
+        );
+        // Opposite infinities cancel out rather than summing to NaN.
+        assert_eq!(
+            combine(&mut db, Operation::Union, &["2", "high", "low"]),
+            scores(&[("x", 0.0), ("y", 1.0)])
+        );
+    }
+
+    #[test]
+    fn missing_keys_count_as_empty_but_wrong_types_fail() {
+        let mut db = db_with_inputs();
+        call(&mut db, &["SET", "string", "1"]);
+        assert_eq!(
+            combine(&mut db, Operation::Union, &["2", "a", "missing"]),
+            scores(&[("x", 1.0), ("y", 2.0)])
+        );
+        assert!(combine(&mut db, Operation::Inter, &["2", "a", "missing"]).is_empty());
+        assert_eq!(
+            call(&mut db, &["ZINTER", "3", "missing", "a", "string"]),
+            b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+        );
+    }
+
+    #[test]
+    fn numkeys_commands_have_movable_keys() {
+        let mut db = Db::default();
+        assert_eq!(
+            call(&mut db, &["COMMAND", "INFO", "zunionstore", "zinter"]),
+            b"*2\r\n\
+              *6\r\n$11\r\nzunionstore\r\n:-4\r\n*2\r\n+write\r\n+movablekeys\r\n:1\r\n:1\r\n:1\r\n\
+              *6\r\n$6\r\nzinter\r\n:-3\r\n*2\r\n+readonly\r\n+movablekeys\r\n:0\r\n:0\r\n:0\r\n"
+        );
+    }
+}
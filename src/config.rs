@@ -0,0 +1,277 @@
+use std::path::Path;
+
+use crate::{
+    connection::{ClientClass, OutputBufferLimit, OutputBufferLimits},
+    error::ServerError,
+    glob::glob_match,
+    resp::RESP,
+};
+
+/// Server settings, read from an optional redis.conf-style file followed by
+/// `--name value` overrides on the command line.
+pub struct Config {
+    pub bind: Vec<String>,
+    pub port: u16,
+    pub dir: String,
+    pub dbfilename: String,
+    pub hz: u32,
+    pub output_buffer_limits: OutputBufferLimits,
+    pub list_max_listpack_size: i64,
+    pub hash_max_listpack_entries: usize,
+    pub hash_max_listpack_value: usize,
+    pub set_max_intset_entries: usize,
+    pub zset_max_listpack_entries: usize,
+    pub zset_max_listpack_value: usize,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            bind: vec!["127.0.0.1".to_string()],
+            port: 6379,
+            dir: std::env::current_dir()
+                .map(|dir| dir.display().to_string())
+                .unwrap_or_else(|_| ".".to_string()),
+            dbfilename: "dump.rdb".to_string(),
+            hz: 10,
+            output_buffer_limits: OutputBufferLimits::default(),
+            list_max_listpack_size: -2,
+            hash_max_listpack_entries: 128,
+            hash_max_listpack_value: 64,
+            set_max_intset_entries: 512,
+            zset_max_listpack_entries: 128,
+            zset_max_listpack_value: 64,
+        }
+    }
+}
+
+impl Config {
+    /// Parameter names in the order `CONFIG GET *` lists them.
+    const NAMES: &'static [&'static str] = &[
+        "bind",
+        "port",
+        "dir",
+        "dbfilename",
+        "hz",
+        "client-output-buffer-limit",
+        "list-max-listpack-size",
+        "hash-max-listpack-entries",
+        "hash-max-listpack-value",
+        "set-max-intset-entries",
+        "zset-max-listpack-entries",
+        "zset-max-listpack-value",
+    ];
+
+    /// Builds the configuration from the process arguments (without the
+    /// program name), as in `redis-server [/path/to/redis.conf] [--name value ...]`.
+    pub fn from_args<I>(args: I) -> Result<Config, ServerError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter().peekable();
+        let mut text = String::new();
+
+        if let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+            text = std::fs::read_to_string(&path)
+                .map_err(|source| ServerError::ConfigFile { path, source })?;
+            text.push('\n');
+        }
+
+        // Each `--name` starts a new directive, and every argument up to the
+        // next one becomes one of its values.
+        for arg in args {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    text.push('\n');
+                    text.push_str(name);
+                }
+                None => {
+                    text.push(' ');
+                    text.push_str(&quote(&arg));
+                }
+            }
+        }
+
+        let mut config = Config::default();
+        config.load(&text)?;
+        Ok(config)
+    }
+
+    fn load(&mut self, text: &str) -> Result<(), ServerError> {
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |reason: String| ServerError::Config {
+                line: number + 1,
+                directive: line.to_string(),
+                reason,
+            };
+            let args = RESP::split_inline_args(line.as_bytes()).map_err(error)?;
+            let args: Vec<String> = args
+                .iter()
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            if let Some((name, values)) = args.split_first() {
+                self.set(&name.to_ascii_lowercase(), values)
+                    .map_err(error)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, name: &str, values: &[String]) -> Result<(), String> {
+        let single = || match values {
+            [value] => Ok(value.as_str()),
+            _ => Err("wrong number of arguments".to_string()),
+        };
+
+        match name {
+            "bind" if !values.is_empty() => self.bind = values.to_vec(),
+            "port" => self.port = single()?.parse().map_err(|_| "Invalid port".to_string())?,
+            "dir" => {
+                let dir = single()?;
+                std::env::set_current_dir(dir)
+                    .map_err(|e| format!("Can't chdir to '{}': {}", dir, e))?;
+                self.dir = std::env::current_dir()
+                    .map(|dir| dir.display().to_string())
+                    .unwrap_or_else(|_| dir.to_string());
+            }
+            "dbfilename" => {
+                let filename = single()?;
+                if Path::new(filename).file_name().map(|f| f.len()) != Some(filename.len()) {
+                    return Err("dbfilename can't be a path, just a filename".to_string());
+                }
+                self.dbfilename = filename.to_string();
+            }
+            "hz" => {
+                let hz: u32 = single()?
+                    .parse()
+                    .map_err(|_| "Invalid hz value".to_string())?;
+                self.hz = hz.clamp(1, 500);
+            }
+            "client-output-buffer-limit"
+                if !values.is_empty() && values.chunks_exact(4).remainder().is_empty() =>
+            {
+                for group in values.chunks_exact(4) {
+                    let class = match group[0].to_ascii_lowercase().as_str() {
+                        "normal" => ClientClass::Normal,
+                        "replica" | "slave" => ClientClass::Replica,
+                        "pubsub" => ClientClass::PubSub,
+                        other => return Err(format!("Invalid client class '{}'", other)),
+                    };
+                    let limit = OutputBufferLimit {
+                        hard: parse_memory(&group[1]).ok_or("Invalid hard limit")?,
+                        soft: parse_memory(&group[2]).ok_or("Invalid soft limit")?,
+                        soft_seconds: group[3].parse().map_err(|_| "Invalid soft seconds")?,
+                    };
+                    self.output_buffer_limits.set(class, limit);
+                }
+            }
+            "list-max-listpack-size" | "list-max-ziplist-size" => {
+                self.list_max_listpack_size = single()?
+                    .parse()
+                    .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            }
+            "hash-max-listpack-entries" | "hash-max-ziplist-entries" => {
+                self.hash_max_listpack_entries = single()?
+                    .parse()
+                    .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            }
+            "hash-max-listpack-value" | "hash-max-ziplist-value" => {
+                self.hash_max_listpack_value = single()?
+                    .parse()
+                    .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            }
+            "set-max-intset-entries" => {
+                self.set_max_intset_entries = single()?
+                    .parse()
+                    .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            }
+            "zset-max-listpack-entries" | "zset-max-ziplist-entries" => {
+                self.zset_max_listpack_entries = single()?
+                    .parse()
+                    .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            }
+            "zset-max-listpack-value" | "zset-max-ziplist-value" => {
+                self.zset_max_listpack_value = single()?
+                    .parse()
+                    .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+            }
+            _ => return Err("Bad directive or wrong number of arguments".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "bind" => self.bind.join(" "),
+            "port" => self.port.to_string(),
+            "dir" => self.dir.clone(),
+            "dbfilename" => self.dbfilename.clone(),
+            "hz" => self.hz.to_string(),
+            "client-output-buffer-limit" => [
+                ("normal", ClientClass::Normal),
+                ("slave", ClientClass::Replica),
+                ("pubsub", ClientClass::PubSub),
+            ]
+            .iter()
+            .map(|(name, class)| {
+                let limit = self.output_buffer_limits.for_class(*class);
+                format!(
+                    "{} {} {} {}",
+                    name, limit.hard, limit.soft, limit.soft_seconds
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+            "list-max-listpack-size" => self.list_max_listpack_size.to_string(),
+            "hash-max-listpack-entries" => self.hash_max_listpack_entries.to_string(),
+            "hash-max-listpack-value" => self.hash_max_listpack_value.to_string(),
+            "set-max-intset-entries" => self.set_max_intset_entries.to_string(),
+            "zset-max-listpack-entries" => self.zset_max_listpack_entries.to_string(),
+            "zset-max-listpack-value" => self.zset_max_listpack_value.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Returns the `(name, value)` pairs of every parameter matching one of
+    /// the glob `patterns`, case-insensitively.
+    pub fn matching(&self, patterns: &[&[u8]]) -> Vec<(&'static str, String)> {
+        Config::NAMES
+            .iter()
+            .filter(|name| {
+                patterns
+                    .iter()
+                    .any(|pattern| glob_match(pattern, name.as_bytes(), true))
+            })
+            .filter_map(|name| Some((*name, self.get(name)?)))
+            .collect()
+    }
+}
+
+/// Parses a memory amount such as `512`, `64mb` or `1gb` (case-insensitive,
+/// `k`/`m`/`g` being powers of 1000 and `kb`/`mb`/`gb` powers of 1024).
+pub fn parse_memory(value: &str) -> Option<usize> {
+    let value = value.to_ascii_lowercase();
+    let digits = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier = match &value[digits.len()..] {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+/// Quotes a command-line value so it survives being re-split as a config line.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
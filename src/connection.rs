@@ -0,0 +1,209 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+use crate::resp::RequestDecoder;
+
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+/// What the per-connection reader tasks report back to the server loop.
+pub enum ConnectionEvent {
+    Data(usize, Bytes),
+    Closed(usize),
+    Failed(usize, std::io::Error),
+}
+
+/// Client classes that get their own output buffer limits, as in Redis.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ClientClass {
+    Normal,
+    Replica,
+    PubSub,
+}
+
+/// Limits on how many reply bytes may wait for a slow client. A client is
+/// disconnected as soon as it reaches `hard` bytes, or once it has stayed at or
+/// above `soft` bytes for `soft_seconds`. A limit of zero disables that check.
+#[derive(Clone, Copy)]
+pub struct OutputBufferLimit {
+    pub hard: usize,
+    pub soft: usize,
+    pub soft_seconds: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct OutputBufferLimits {
+    pub normal: OutputBufferLimit,
+    pub replica: OutputBufferLimit,
+    pub pubsub: OutputBufferLimit,
+}
+
+impl OutputBufferLimits {
+    pub fn for_class(&self, class: ClientClass) -> OutputBufferLimit {
+        match class {
+            ClientClass::Normal => self.normal,
+            ClientClass::Replica => self.replica,
+            ClientClass::PubSub => self.pubsub,
+        }
+    }
+
+    pub fn set(&mut self, class: ClientClass, limit: OutputBufferLimit) {
+        match class {
+            ClientClass::Normal => self.normal = limit,
+            ClientClass::Replica => self.replica = limit,
+            ClientClass::PubSub => self.pubsub = limit,
+        }
+    }
+}
+
+impl Default for OutputBufferLimits {
+    fn default() -> OutputBufferLimits {
+        const MB: usize = 1024 * 1024;
+        OutputBufferLimits {
+            normal: OutputBufferLimit {
+                hard: 0,
+                soft: 0,
+                soft_seconds: 0,
+            },
+            replica: OutputBufferLimit {
+                hard: 256 * MB,
+                soft: 64 * MB,
+                soft_seconds: 60,
+            },
+            pubsub: OutputBufferLimit {
+                hard: 32 * MB,
+                soft: 8 * MB,
+                soft_seconds: 60,
+            },
+        }
+    }
+}
+
+pub struct Connection {
+    pub id: usize,
+    pub buffer: BytesMut,
+    pub decoder: RequestDecoder,
+    output: mpsc::UnboundedSender<Bytes>,
+    /// Bytes handed to the writer task that have not reached the socket yet.
+    pending_output: Arc<AtomicUsize>,
+    output_limit: OutputBufferLimit,
+    soft_limit_reached_at: Option<Instant>,
+    reader: JoinHandle<()>,
+    writer: JoinHandle<()>,
+}
+
+impl Connection {
+    pub fn new(
+        id: usize,
+        stream: TcpStream,
+        class: ClientClass,
+        limits: &OutputBufferLimits,
+        events: mpsc::Sender<ConnectionEvent>,
+    ) -> Connection {
+        let (read_half, write_half) = stream.into_split();
+        let (output, queued) = mpsc::unbounded_channel();
+        let pending_output = Arc::new(AtomicUsize::new(0));
+
+        Connection {
+            id,
+            buffer: BytesMut::new(),
+            decoder: RequestDecoder::default(),
+            output,
+            pending_output: pending_output.clone(),
+            output_limit: limits.for_class(class),
+            soft_limit_reached_at: None,
+            reader: tokio::spawn(read_requests(id, read_half, events)),
+            writer: tokio::spawn(write_replies(write_half, queued, pending_output)),
+        }
+    }
+
+    /// Queues replies for the writer task, which flushes them as the socket
+    /// accepts more data. Returns `false` if the client has gone over its
+    /// output buffer limit, in which case the queued output is discarded and
+    /// the connection should be closed.
+    pub fn send(&mut self, replies: Bytes) -> bool {
+        let pending = self
+            .pending_output
+            .fetch_add(replies.len(), Ordering::AcqRel)
+            + replies.len();
+        // The writer task only goes away once the socket is gone, in which
+        // case the reader reports the close and the connection is dropped.
+        let _ = self.output.send(replies);
+
+        if self.over_output_limit(pending) {
+            self.writer.abort();
+            return false;
+        }
+        true
+    }
+
+    fn over_output_limit(&mut self, pending: usize) -> bool {
+        let limit = self.output_limit;
+        if limit.hard > 0 && pending >= limit.hard {
+            return true;
+        }
+        if limit.soft == 0 || pending < limit.soft {
+            self.soft_limit_reached_at = None;
+            return false;
+        }
+
+        let since = *self.soft_limit_reached_at.get_or_insert_with(Instant::now);
+        since.elapsed() >= Duration::from_secs(limit.soft_seconds)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn read_requests(
+    id: usize,
+    mut stream: OwnedReadHalf,
+    events: mpsc::Sender<ConnectionEvent>,
+) {
+    let mut buffer = BytesMut::with_capacity(READ_CHUNK_SIZE);
+    loop {
+        buffer.reserve(READ_CHUNK_SIZE);
+        let event = match stream.read_buf(&mut buffer).await {
+            Ok(0) => ConnectionEvent::Closed(id),
+            Ok(_) => ConnectionEvent::Data(id, buffer.split().freeze()),
+            Err(e) => ConnectionEvent::Failed(id, e),
+        };
+
+        let done = !matches!(event, ConnectionEvent::Data(..));
+        if events.send(event).await.is_err() || done {
+            return;
+        }
+    }
+}
+
+async fn write_replies(
+    mut stream: OwnedWriteHalf,
+    mut queued: mpsc::UnboundedReceiver<Bytes>,
+    pending_output: Arc<AtomicUsize>,
+) {
+    while let Some(replies) = queued.recv().await {
+        if let Err(e) = stream.write_all(&replies).await {
+            eprintln!("Error writing to stream: {}", e);
+            return;
+        }
+        pending_output.fetch_sub(replies.len(), Ordering::AcqRel);
+    }
+}
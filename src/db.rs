@@ -0,0 +1,292 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    dict::Dict,
+    types::{Hash, List, Set, SortedSet, StringValue},
+};
+
+/// How many keys with an expiry the active expire cycle samples per round.
+const EXPIRE_KEYS_PER_LOOP: usize = 20;
+/// The active expire cycle keeps sampling while more than this percentage of
+/// the sampled keys turn out to be expired.
+const EXPIRE_ACCEPTABLE_STALE_PERCENT: usize = 10;
+
+/// A value stored in the keyspace.
+pub enum Value {
+    String(StringValue),
+    List(List),
+    Hash(Hash),
+    Set(Set),
+    SortedSet(SortedSet),
+}
+
+impl Value {
+    /// The name TYPE would reply with.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::List(_) => "list",
+            Value::Hash(_) => "hash",
+            Value::Set(_) => "set",
+            Value::SortedSet(_) => "zset",
+        }
+    }
+}
+
+/// The keyspace: every key the server holds, plus the expiry times (unix
+/// milliseconds) of the keys that have one.
+///
+/// Expired keys are removed lazily when they are next looked up, and
+/// proactively by `active_expire_cycle`. The same goes for the expired
+/// fields of hashes, which are tracked per key in `volatile_hashes`.
+///
+/// The keyspace also tracks which clients are blocked waiting for which
+/// keys, so that creating one of those keys can mark it ready for the
+/// server to serve the waiters.
+#[derive(Default)]
+pub struct Db {
+    entries: HashMap<Bytes, Value>,
+    expires: Dict<Bytes, u64>,
+    /// Keys holding hashes that have fields with an expiry time. Entries can
+    /// go stale when the hash is replaced, and are dropped once noticed.
+    volatile_hashes: Dict<Bytes, ()>,
+    /// Blocked client ids per key, in the order they blocked.
+    blocking_keys: HashMap<Bytes, VecDeque<usize>>,
+    ready_keys: Vec<Bytes>,
+}
+
+/// Milliseconds since the unix epoch.
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+impl Db {
+    /// Removes `key` if its expiry time has passed, returning whether it did.
+    /// Expired hash fields are reclaimed too, which deletes the key when
+    /// there are none left.
+    fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        match self.expires.get(key) {
+            Some(&when) if when <= now_ms() => {
+                self.remove(key);
+                true
+            }
+            _ if self.volatile_hashes.get(key).is_some() => {
+                self.expire_fields(key, now_ms());
+                !self.entries.contains_key(key)
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes the fields of the hash at `key` that expired by `now`,
+    /// returning how many there were. Emptying the hash deletes the key,
+    /// and a hash left without field expiries stops being tracked.
+    fn expire_fields(&mut self, key: &[u8], now: u64) -> usize {
+        let Some(Value::Hash(hash)) = self.entries.get_mut(key) else {
+            self.volatile_hashes.remove(key);
+            return 0;
+        };
+        let removed = hash.remove_expired_fields(now);
+        if hash.is_empty() {
+            self.remove(key);
+        } else if !hash.has_field_expiries() {
+            self.volatile_hashes.remove(key);
+        }
+        removed
+    }
+
+    pub fn get(&mut self, key: &[u8]) -> Option<&Value> {
+        self.expire_if_needed(key);
+        self.entries.get(key)
+    }
+
+    /// Looks up several keys at once, expiring them as `get` does, so that
+    /// their values can be read side by side.
+    pub fn get_many(&mut self, keys: &[Bytes]) -> Vec<Option<&Value>> {
+        for key in keys {
+            self.expire_if_needed(key);
+        }
+        keys.iter().map(|key| self.entries.get(key)).collect()
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Value> {
+        self.expire_if_needed(key);
+        self.entries.get_mut(key)
+    }
+
+    pub fn contains(&mut self, key: &[u8]) -> bool {
+        self.expire_if_needed(key);
+        self.entries.contains_key(key)
+    }
+
+    /// Stores `value` under `key`, replacing whatever was there. Any expiry on
+    /// the key is cleared unless `keep_ttl` is set.
+    pub fn set(&mut self, key: Bytes, value: Value, keep_ttl: bool) {
+        if !keep_ttl {
+            self.expires.remove(&key);
+        }
+        if self.blocking_keys.contains_key(&key) && !self.ready_keys.contains(&key) {
+            self.ready_keys.push(key.clone());
+        }
+        if matches!(&value, Value::Hash(hash) if hash.has_field_expiries()) {
+            self.volatile_hashes.insert(key.clone(), ());
+        }
+        self.entries.insert(key, value);
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Value> {
+        self.expires.remove(key);
+        self.volatile_hashes.remove(key);
+        self.entries.remove(key)
+    }
+
+    /// The expiry time of `key` in unix milliseconds, if it has one.
+    pub fn expiry(&mut self, key: &[u8]) -> Option<u64> {
+        self.expire_if_needed(key);
+        self.expires.get(key).copied()
+    }
+
+    /// Sets the expiry time of an existing key. A time that has already
+    /// passed deletes the key straight away.
+    pub fn set_expiry(&mut self, key: &[u8], when: u64) {
+        let Some((key, _)) = self.entries.get_key_value(key) else {
+            return;
+        };
+        if when <= now_ms() {
+            let key = key.clone();
+            self.remove(&key);
+        } else {
+            self.expires.insert(key.clone(), when);
+        }
+    }
+
+    /// Removes the expiry of `key`, returning whether it had one.
+    pub fn persist(&mut self, key: &[u8]) -> bool {
+        !self.expire_if_needed(key) && self.expires.remove(key).is_some()
+    }
+
+    /// Starts tracking the hash at `key` if it has fields with an expiry
+    /// time, so that they get reclaimed even if nobody reads them. Called
+    /// after giving fields of an existing hash an expiry.
+    pub fn track_field_expiries(&mut self, key: &[u8]) {
+        if let Some((key, Value::Hash(hash))) = self.entries.get_key_value(key) {
+            if hash.has_field_expiries() {
+                self.volatile_hashes.insert(key.clone(), ());
+            }
+        }
+    }
+
+    /// Registers client `id` as waiting for any of `keys` to be created.
+    pub fn block_client(&mut self, id: usize, keys: &[Bytes]) {
+        for key in keys {
+            let waiters = self.blocking_keys.entry(key.clone()).or_default();
+            if !waiters.contains(&id) {
+                waiters.push_back(id);
+            }
+        }
+    }
+
+    pub fn unblock_client(&mut self, id: usize, keys: &[Bytes]) {
+        for key in keys {
+            if let Some(waiters) = self.blocking_keys.get_mut(key) {
+                waiters.retain(|&waiter| waiter != id);
+                if waiters.is_empty() {
+                    self.blocking_keys.remove(key);
+                }
+            }
+        }
+    }
+
+    /// The clients blocked on `key`, longest waiting first.
+    pub fn blocked_clients(&self, key: &[u8]) -> Vec<usize> {
+        self.blocking_keys
+            .get(key)
+            .map(|waiters| waiters.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn has_ready_keys(&self) -> bool {
+        !self.ready_keys.is_empty()
+    }
+
+    /// Takes the keys that were created while clients were blocked on them.
+    pub fn take_ready_keys(&mut self) -> Vec<Bytes> {
+        std::mem::take(&mut self.ready_keys)
+    }
+
+    /// Samples keys that have an expiry and deletes the expired ones, going
+    /// round after round while a large share of each sample was expired and
+    /// `time_limit` has not run out, then does the same for the fields of
+    /// hashes. This bounds both the memory held by data nobody reads and the
+    /// time taken away from serving clients.
+    pub fn active_expire_cycle(&mut self, time_limit: Duration) {
+        let deadline = Instant::now() + time_limit;
+        self.expire_keys_until(deadline);
+        self.expire_fields_until(deadline);
+    }
+
+    fn expire_keys_until(&mut self, deadline: Instant) {
+        loop {
+            let sample = self.expires.len().min(EXPIRE_KEYS_PER_LOOP);
+            if sample == 0 {
+                return;
+            }
+
+            let now = now_ms();
+            let mut expired = 0;
+            for _ in 0..sample {
+                let Some((key, &when)) = self.expires.random_entry() else {
+                    break;
+                };
+                if when <= now {
+                    let key = key.clone();
+                    self.remove(&key);
+                    expired += 1;
+                }
+            }
+
+            if expired * 100 <= sample * EXPIRE_ACCEPTABLE_STALE_PERCENT
+                || Instant::now() >= deadline
+            {
+                return;
+            }
+        }
+    }
+
+    /// Like `expire_keys_until`, sampling hashes with field expiries and
+    /// counting those that had any expired fields.
+    fn expire_fields_until(&mut self, deadline: Instant) {
+        loop {
+            let sample = self.volatile_hashes.len().min(EXPIRE_KEYS_PER_LOOP);
+            if sample == 0 {
+                return;
+            }
+
+            let now = now_ms();
+            let mut expired = 0;
+            for _ in 0..sample {
+                let Some((key, _)) = self.volatile_hashes.random_entry() else {
+                    break;
+                };
+                let key = key.clone();
+                if self.expire_fields(&key, now) > 0 {
+                    expired += 1;
+                }
+            }
+
+            if expired * 100 <= sample * EXPIRE_ACCEPTABLE_STALE_PERCENT
+                || Instant::now() >= deadline
+            {
+                return;
+            }
+        }
+    }
+}
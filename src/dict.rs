@@ -0,0 +1,271 @@
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+};
+
+use crate::random::random_index;
+
+const INITIAL_SIZE: usize = 4;
+/// Shrink once fewer than this percentage of buckets would be in use.
+const MIN_FILL_PERCENT: usize = 10;
+
+/// A chained hash table in the style of Redis' `dict`.
+///
+/// Unlike `HashMap` it exposes its bucket layout, which is what makes it
+/// possible to pick random entries cheaply and to offer SCAN cursors that
+/// return every entry present for the whole scan at least once, even if the
+/// table is resized between calls.
+pub struct Dict<K, V> {
+    buckets: Vec<Vec<(K, V)>>,
+    len: usize,
+    hasher: RandomState,
+}
+
+impl<K, V> Default for Dict<K, V> {
+    fn default() -> Dict<K, V> {
+        Dict {
+            buckets: Vec::new(),
+            len: 0,
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> Dict<K, V> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    fn bucket_index<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        self.hasher.hash_one(key) as usize & (self.buckets.len() - 1)
+    }
+
+    fn position<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let bucket = self.bucket_index(key);
+        let slot = self.buckets[bucket]
+            .iter()
+            .position(|(k, _)| k.borrow() == key)?;
+        Some((bucket, slot))
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (bucket, slot) = self.position(key)?;
+        Some(&self.buckets[bucket][slot].1)
+    }
+
+    /// Inserts or replaces the value for `key`, returning the old value.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some((bucket, slot)) = self.position(&key) {
+            return Some(std::mem::replace(&mut self.buckets[bucket][slot].1, value));
+        }
+
+        if self.len >= self.buckets.len() {
+            self.resize((self.buckets.len() * 2).max(INITIAL_SIZE));
+        }
+        let bucket = self.bucket_index(&key);
+        self.buckets[bucket].push((key, value));
+        self.len += 1;
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (bucket, slot) = self.position(key)?;
+        let entry = self.buckets[bucket].swap_remove(slot);
+        self.len -= 1;
+
+        let size = self.buckets.len();
+        if size > INITIAL_SIZE && self.len * 100 / size < MIN_FILL_PERCENT {
+            self.resize(self.len.next_power_of_two().max(INITIAL_SIZE));
+        }
+        Some(entry)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets
+            .iter()
+            .flatten()
+            .map(|(key, value)| (key, value))
+    }
+
+    fn resize(&mut self, size: usize) {
+        let old = std::mem::replace(&mut self.buckets, (0..size).map(|_| Vec::new()).collect());
+        for (key, value) in old.into_iter().flatten() {
+            let bucket = self.bucket_index(&key);
+            self.buckets[bucket].push((key, value));
+        }
+    }
+
+    /// Returns a random entry. Entries in sparsely filled buckets are
+    /// slightly favoured, which is fine for sampling.
+    pub fn random_entry(&self) -> Option<(&K, &V)> {
+        if self.len == 0 {
+            return None;
+        }
+        loop {
+            let bucket = &self.buckets[random_index(self.buckets.len())];
+            if !bucket.is_empty() {
+                let (key, value) = &bucket[random_index(bucket.len())];
+                return Some((key, value));
+            }
+        }
+    }
+
+    /// Visits entries from `cursor` on, bucket by bucket, until roughly
+    /// `count` have been visited, and returns the cursor to continue from,
+    /// or 0 once the whole table has been covered. Empty buckets count
+    /// towards the budget too, so that a sparse table cannot make a single
+    /// call walk all of it.
+    pub fn scan(&self, cursor: u64, count: usize, mut visit: impl FnMut(&K, &V)) -> u64 {
+        let mut cursor = cursor;
+        let mut visited = 0;
+        for _ in 0..count.saturating_mul(10) {
+            cursor = self.scan_bucket(cursor, |key, value| {
+                visit(key, value);
+                visited += 1;
+            });
+            if cursor == 0 || visited >= count {
+                break;
+            }
+        }
+        cursor
+    }
+
+    /// Visits the entries of the bucket `cursor` points to and returns the
+    /// cursor for the next bucket, or 0 once the whole table has been
+    /// covered.
+    ///
+    /// Cursors advance by incrementing their reversed bits, so buckets that a
+    /// table split or merge maps onto an already visited bucket are never
+    /// revisited from scratch and none are skipped.
+    fn scan_bucket(&self, cursor: u64, mut visit: impl FnMut(&K, &V)) -> u64 {
+        if self.len == 0 {
+            return 0;
+        }
+
+        let mask = self.buckets.len() as u64 - 1;
+        for (key, value) in &self.buckets[(cursor & mask) as usize] {
+            visit(key, value);
+        }
+
+        (cursor | !mask)
+            .reverse_bits()
+            .wrapping_add(1)
+            .reverse_bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// Scans `dict` to completion, calling `between` after every call, and
+    /// returns the keys seen.
+    fn scan_all(
+        dict: &mut Dict<u32, ()>,
+        mut between: impl FnMut(&mut Dict<u32, ()>, usize),
+    ) -> HashSet<u32> {
+        let mut seen = HashSet::new();
+        let mut cursor = 0;
+        let mut calls = 0;
+        loop {
+            cursor = dict.scan(cursor, 10, |&key, _| {
+                seen.insert(key);
+            });
+            if cursor == 0 {
+                return seen;
+            }
+            between(dict, calls);
+            calls += 1;
+        }
+    }
+
+    #[test]
+    fn scan_visits_every_entry_once_without_resizes() {
+        let mut dict = Dict::default();
+        for key in 0..1000 {
+            dict.insert(key, ());
+        }
+        let mut visits = Vec::new();
+        let mut cursor = 0;
+        loop {
+            cursor = dict.scan(cursor, 10, |&key, _| visits.push(key));
+            if cursor == 0 {
+                break;
+            }
+        }
+        visits.sort_unstable();
+        assert_eq!(visits, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scan_survives_growing_and_shrinking_mid_scan() {
+        let mut dict = Dict::default();
+        for key in 0..300 {
+            dict.insert(key, ());
+        }
+
+        // Keys from 10_000 up come and go while the scan runs, taking the
+        // table through several rounds of growing and shrinking.
+        let mut extra = 10_000..10_000;
+        let seen = scan_all(&mut dict, |dict, call| {
+            if call % 8 < 4 {
+                for _ in 0..500 {
+                    dict.insert(extra.end, ());
+                    extra.end += 1;
+                }
+            } else {
+                while extra.len() > 100 {
+                    dict.remove(&extra.start);
+                    extra.start += 1;
+                }
+            }
+        });
+        for key in 0..300 {
+            assert!(seen.contains(&key), "missed {key}");
+        }
+    }
+
+    #[test]
+    fn scan_survives_shrinking_to_the_minimum_size() {
+        let mut dict = Dict::default();
+        for key in 0..4000 {
+            dict.insert(key, ());
+        }
+        let seen = scan_all(&mut dict, |dict, call| {
+            if call == 0 {
+                for key in 10..4000 {
+                    dict.remove(&key);
+                }
+            }
+        });
+        for key in 0..10 {
+            assert!(seen.contains(&key), "missed {key}");
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use std::io;
+
+/// Errors that stop the server from starting. Anything that goes wrong with
+/// a single client only closes that client's connection.
+#[derive(Debug, thiserror::Error)]
+pub enum ServerError {
+    #[error("Could not create server TCP listening socket {addr}: {source}")]
+    Bind {
+        addr: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Fatal error, can't open config file '{path}': {source}")]
+    ConfigFile {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("FATAL CONFIG FILE ERROR at line {line}\n>>> '{directive}'\n{reason}")]
+    Config {
+        line: usize,
+        directive: String,
+        reason: String,
+    },
+}
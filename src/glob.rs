@@ -0,0 +1,163 @@
+/// Glob-style matching with the same rules as Redis' `stringmatchlen`:
+/// `*`, `?`, `[abc]`, `[^abc]`, `[a-z]` and `\` to escape the next byte.
+///
+/// Every token but `*` matches exactly one byte, so on a mismatch it is
+/// enough to let the last `*` seen absorb one more byte and retry from
+/// there. Earlier stars never need revisiting, which keeps matching at
+/// O(pattern × string) where recursive backtracking is exponential.
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Where to resume after the last `*`: the pattern index following it
+    // and the string index it has absorbed up to.
+    let mut retry = None;
+
+    while s < string.len() {
+        if pattern.get(p) == Some(&b'*') {
+            while pattern.get(p) == Some(&b'*') {
+                p += 1;
+            }
+            if p == pattern.len() {
+                return true;
+            }
+            retry = Some((p, s));
+            continue;
+        }
+
+        if let Some(next) = match_token(pattern, p, string[s], nocase) {
+            p = next;
+            s += 1;
+            continue;
+        }
+        let Some((star_p, star_s)) = retry else {
+            return false;
+        };
+        retry = Some((star_p, star_s + 1));
+        p = star_p;
+        s = star_s + 1;
+    }
+
+    while pattern.get(p) == Some(&b'*') {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
+/// Matches the token at `pattern[p]`, which is not `*`, against `c`,
+/// returning the index of the following token if it matches.
+fn match_token(pattern: &[u8], mut p: usize, c: u8, nocase: bool) -> Option<usize> {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+
+    let matched = match *pattern.get(p)? {
+        b'?' => true,
+        b'[' => {
+            p += 1;
+            let negate = pattern.get(p) == Some(&b'^');
+            if negate {
+                p += 1;
+            }
+
+            let mut matched = false;
+            loop {
+                match pattern.get(p) {
+                    None => {
+                        // Unterminated class: stop at the end of the pattern.
+                        p -= 1;
+                        break;
+                    }
+                    Some(b'\\') if p + 1 < pattern.len() => {
+                        p += 1;
+                        matched |= eq(pattern[p], c);
+                    }
+                    Some(b']') => break,
+                    Some(&start) if p + 2 < pattern.len() && pattern[p + 1] == b'-' => {
+                        let end = pattern[p + 2];
+                        let (mut start, mut end, mut c) = (start.min(end), start.max(end), c);
+                        if nocase {
+                            start = start.to_ascii_lowercase();
+                            end = end.to_ascii_lowercase();
+                            c = c.to_ascii_lowercase();
+                        }
+                        p += 2;
+                        matched |= start <= c && c <= end;
+                    }
+                    Some(&other) => matched |= eq(other, c),
+                }
+                p += 1;
+            }
+            matched != negate
+        }
+        b'\\' if p + 1 < pattern.len() => {
+            p += 1;
+            eq(pattern[p], c)
+        }
+        other => eq(other, c),
+    };
+    matched.then_some(p + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn matches_like_redis() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("*", "", true),
+            ("*", "anything", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("h*llo", "hllo", true),
+            ("h*llo", "heeeello", true),
+            ("h*llo*", "hello world", true),
+            ("*llo", "hello!", false),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-b]llo", "hbllo", true),
+            ("h[b-a]llo", "hallo", true),
+            ("h[a-b]llo", "hcllo", false),
+            ("h\\*llo", "h*llo", true),
+            ("h\\*llo", "hello", false),
+            ("h[\\]]llo", "h]llo", true),
+            ("a[bc", "ab", true),
+            ("a[bc", "ad", false),
+            ("ab\\", "ab\\", true),
+            ("a*b*c", "aXbYbZc", true),
+            ("a*b*c", "aXbYbZ", false),
+            ("", "", true),
+            ("", "a", false),
+        ];
+        for &(pattern, string, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), string.as_bytes(), false),
+                expected,
+                "{pattern:?} against {string:?}"
+            );
+        }
+        assert!(glob_match(b"H[A-B]LLO", b"hbllo", true));
+        assert!(!glob_match(b"H[A-B]LLO", b"hbllo", false));
+        assert!(glob_match(b"h[XY]llo", b"hyllo", true));
+        assert!(glob_match(b"h[^XY]llo", b"hallo", true));
+        assert!(!glob_match(b"h[^XY]llo", b"hyllo", true));
+        assert!(glob_match(b"h[\\E]llo", b"hello", true));
+        assert!(!glob_match(b"h[\\E]llo", b"hello", false));
+    }
+
+    #[test]
+    fn pathological_patterns_run_in_polynomial_time() {
+        let pattern = "a*".repeat(30) + "b";
+        let string = vec![b'a'; 10_000];
+        let started = Instant::now();
+        assert!(!glob_match(pattern.as_bytes(), &string, false));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
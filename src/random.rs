@@ -0,0 +1,27 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+thread_local! {
+    static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// A fast, non-cryptographic random number (xorshift64*), good enough for
+/// sampling keys and picking random members.
+pub fn random_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    })
+}
+
+/// A random index in `0..n`. `n` must not be zero.
+pub fn random_index(n: usize) -> usize {
+    (random_u64() % n as u64) as usize
+}
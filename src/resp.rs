@@ -0,0 +1,462 @@
+use bytes::{Bytes, BytesMut};
+
+#[allow(clippy::upper_case_acronyms)]
+pub enum RESP {
+    SimpleString(String),
+    Error(String),
+    Integer(i64),
+    BulkString(Bytes),
+    NullBulkString,
+    Array(Vec<RESP>),
+    NullArray,
+}
+
+const MAX_BULK_LENGTH: i64 = 512 * 1024 * 1024;
+const MAX_MULTIBULK_LENGTH: i64 = 1024 * 1024;
+const MAX_HEADER_LENGTH: usize = 64 * 1024;
+const MAX_INLINE_LENGTH: usize = 64 * 1024;
+
+/// Decodes client requests: multibulk frames, which are arrays of bulk
+/// strings, or inline commands as typed into `telnet` or `nc`.
+///
+/// A request that has only partly arrived is decoded as far as it goes, and
+/// the bytes read so far are split off the buffer and kept here, so that the
+/// next call picks up where this one stopped rather than rescanning the
+/// request from its start.
+#[derive(Default)]
+pub struct RequestDecoder {
+    /// The number of arguments the request in progress declared, once its
+    /// header has been read.
+    count: Option<usize>,
+    /// The arguments of the request in progress read so far.
+    args: Vec<Bytes>,
+    /// The declared length of the next argument, once its header has been
+    /// read.
+    bulk_length: Option<usize>,
+    /// The bytes of the request in progress consumed so far.
+    consumed: usize,
+}
+
+impl RequestDecoder {
+    /// Decodes a request from the front of `buf`, splitting off the bytes it
+    /// reads.
+    ///
+    /// Returns `Ok(None)` while the request has not fully arrived. Otherwise
+    /// the request is returned as an array of bulk strings, along with the
+    /// number of bytes it occupied. Bulk strings are slices of the received
+    /// bytes, not copies. Requests are flat, so an argument that is not a
+    /// bulk string is a protocol error. After an error the decoder starts
+    /// afresh with the next request.
+    pub fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<(RESP, usize)>, String> {
+        let result = self.decode_request(buf);
+        if result.is_err() {
+            *self = RequestDecoder::default();
+        }
+        result
+    }
+
+    fn decode_request(&mut self, buf: &mut BytesMut) -> Result<Option<(RESP, usize)>, String> {
+        let count = match self.count {
+            Some(count) => count,
+            None if buf.first() != Some(&b'*') => return decode_inline(buf),
+            None => {
+                let Some(line) = take_line(buf, "too big mbulk count string")? else {
+                    return Ok(None);
+                };
+                let count = match parse_integer(&line[1..]) {
+                    Some(n) if n <= MAX_MULTIBULK_LENGTH => n.max(0) as usize,
+                    _ => return Err("invalid multibulk length".to_string()),
+                };
+                self.consumed = line.len() + 2;
+                self.count = Some(count);
+                self.args = Vec::with_capacity(count.min(1024));
+                count
+            }
+        };
+
+        while self.args.len() < count {
+            let length = match self.bulk_length {
+                Some(length) => length,
+                None => {
+                    match buf.first() {
+                        None => return Ok(None),
+                        Some(b'$') => {}
+                        Some(&other) => {
+                            return Err(format!("expected '$', got '{}'", other as char))
+                        }
+                    }
+                    let Some(line) = take_line(buf, "too big bulk count string")? else {
+                        return Ok(None);
+                    };
+                    let length = match parse_integer(&line[1..]) {
+                        Some(n) if (0..=MAX_BULK_LENGTH).contains(&n) => n as usize,
+                        _ => return Err("invalid bulk length".to_string()),
+                    };
+                    self.consumed += line.len() + 2;
+                    self.bulk_length = Some(length);
+                    length
+                }
+            };
+
+            if buf.len() < length + 2 {
+                return Ok(None);
+            }
+            if &buf[length..length + 2] != b"\r\n" {
+                return Err("bulk string not terminated by CRLF".to_string());
+            }
+            let arg = buf.split_to(length + 2).freeze().slice(..length);
+            self.args.push(arg);
+            self.consumed += length + 2;
+            self.bulk_length = None;
+        }
+
+        self.count = None;
+        let args = std::mem::take(&mut self.args);
+        Ok(Some((
+            RESP::Array(args.into_iter().map(RESP::BulkString).collect()),
+            std::mem::take(&mut self.consumed),
+        )))
+    }
+}
+
+/// Decodes a newline-terminated inline command into an array of bulk
+/// strings. A blank line decodes to an empty array.
+fn decode_inline(buf: &mut BytesMut) -> Result<Option<(RESP, usize)>, String> {
+    let Some(newline) = buf.iter().position(|&b| b == b'\n') else {
+        if buf.len() > MAX_INLINE_LENGTH {
+            return Err("too big inline request".to_string());
+        }
+        return Ok(None);
+    };
+
+    let line = buf.split_to(newline + 1);
+    let args = RESP::split_inline_args(&line)?;
+    Ok(Some((
+        RESP::Array(args.into_iter().map(RESP::BulkString).collect()),
+        line.len(),
+    )))
+}
+
+/// Splits a CRLF-terminated header line off the front of `buf` and returns
+/// it without the CRLF, or `None` if the terminator has not arrived yet. A
+/// header that grows past `MAX_HEADER_LENGTH` fails with `too_big`.
+fn take_line(buf: &mut BytesMut, too_big: &str) -> Result<Option<BytesMut>, String> {
+    let Some(end) = buf.windows(2).position(|w| w == b"\r\n") else {
+        if buf.len() > MAX_HEADER_LENGTH {
+            return Err(too_big.to_string());
+        }
+        return Ok(None);
+    };
+    let mut line = buf.split_to(end + 2);
+    line.truncate(end);
+    Ok(Some(line))
+}
+
+fn parse_integer(digits: &[u8]) -> Option<i64> {
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+impl RESP {
+    /// Splits an inline command line into arguments the same way redis-cli
+    /// does: whitespace separates tokens, double quotes allow `\n`-style and
+    /// `\xHH` escapes, and single quotes only allow `\'`.
+    pub fn split_inline_args(line: &[u8]) -> Result<Vec<Bytes>, String> {
+        let mut args = Vec::new();
+        let mut i = 0;
+
+        loop {
+            while i < line.len() && line[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            if i == line.len() {
+                return Ok(args);
+            }
+
+            let mut current = Vec::new();
+            let mut in_double_quotes = false;
+            let mut in_single_quotes = false;
+            loop {
+                let Some(&c) = line.get(i) else {
+                    if in_double_quotes || in_single_quotes {
+                        return Err("unbalanced quotes in request".to_string());
+                    }
+                    break;
+                };
+
+                if in_double_quotes {
+                    match (c, line.get(i + 1)) {
+                        (b'\\', Some(b'x'))
+                            if line.len() > i + 3
+                                && line[i + 2].is_ascii_hexdigit()
+                                && line[i + 3].is_ascii_hexdigit() =>
+                        {
+                            let hex = std::str::from_utf8(&line[i + 2..i + 4]).unwrap_or_default();
+                            current.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+                            i += 3;
+                        }
+                        (b'\\', Some(&escaped)) => {
+                            current.push(match escaped {
+                                b'n' => b'\n',
+                                b'r' => b'\r',
+                                b't' => b'\t',
+                                b'b' => 0x08,
+                                b'a' => 0x07,
+                                other => other,
+                            });
+                            i += 1;
+                        }
+                        (b'"', next) => {
+                            if next.is_some_and(|n| !n.is_ascii_whitespace()) {
+                                return Err("unbalanced quotes in request".to_string());
+                            }
+                            i += 1;
+                            break;
+                        }
+                        _ => current.push(c),
+                    }
+                } else if in_single_quotes {
+                    match (c, line.get(i + 1)) {
+                        (b'\\', Some(b'\'')) => {
+                            current.push(b'\'');
+                            i += 1;
+                        }
+                        (b'\'', next) => {
+                            if next.is_some_and(|n| !n.is_ascii_whitespace()) {
+                                return Err("unbalanced quotes in request".to_string());
+                            }
+                            i += 1;
+                            break;
+                        }
+                        _ => current.push(c),
+                    }
+                } else {
+                    match c {
+                        b' ' | b'\n' | b'\r' | b'\t' | b'\0' => break,
+                        b'"' => in_double_quotes = true,
+                        b'\'' => in_single_quotes = true,
+                        _ => current.push(c),
+                    }
+                }
+                i += 1;
+            }
+
+            args.push(Bytes::from(current));
+        }
+    }
+
+    /// Appends the wire encoding of this value to `out`.
+    pub fn write_to(&self, out: &mut Vec<u8>) {
+        match self {
+            RESP::SimpleString(s) => {
+                out.push(b'+');
+                out.extend_from_slice(s.as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            RESP::Error(e) => {
+                out.push(b'-');
+                out.extend_from_slice(e.as_bytes());
+                out.extend_from_slice(b"\r\n");
+            }
+            RESP::Integer(n) => {
+                out.extend_from_slice(format!(":{}\r\n", n).as_bytes());
+            }
+            RESP::BulkString(s) => {
+                out.extend_from_slice(format!("${}\r\n", s.len()).as_bytes());
+                out.extend_from_slice(s);
+                out.extend_from_slice(b"\r\n");
+            }
+            RESP::NullBulkString => out.extend_from_slice(b"$-1\r\n"),
+            RESP::Array(values) => {
+                out.extend_from_slice(format!("*{}\r\n", values.len()).as_bytes());
+                for value in values {
+                    value.write_to(out);
+                }
+            }
+            RESP::NullArray => out.extend_from_slice(b"*-1\r\n"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `input` to a decoder in chunks of `chunk` bytes and returns the
+    /// arguments of every request it decodes, or the first error.
+    fn decode_all(input: &[u8], chunk: usize) -> Result<Vec<Vec<Vec<u8>>>, String> {
+        let mut decoder = RequestDecoder::default();
+        let mut buf = BytesMut::new();
+        let mut requests = Vec::new();
+        for piece in input.chunks(chunk) {
+            buf.extend_from_slice(piece);
+            while let Some((request, _)) = decoder.decode(&mut buf)? {
+                let RESP::Array(args) = request else {
+                    panic!("requests decode to arrays");
+                };
+                let args = args
+                    .into_iter()
+                    .map(|arg| match arg {
+                        RESP::BulkString(arg) => arg.to_vec(),
+                        _ => panic!("arguments decode to bulk strings"),
+                    })
+                    .collect();
+                requests.push(args);
+            }
+        }
+        Ok(requests)
+    }
+
+    fn args(args: &[&str]) -> Vec<Vec<u8>> {
+        args.iter().map(|arg| arg.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn decodes_pipelined_requests_split_at_every_byte() {
+        let input = b"*2\r\n$4\r\nECHO\r\n$5\r\nhe\r\no\r\n*1\r\n$4\r\nPING\r\n";
+        for chunk in 1..=input.len() {
+            assert_eq!(
+                decode_all(input, chunk).unwrap(),
+                vec![args(&["ECHO", "he\r\no"]), args(&["PING"])]
+            );
+        }
+    }
+
+    #[test]
+    fn partial_frame_keeps_progress_and_reports_consumed_bytes() {
+        let mut decoder = RequestDecoder::default();
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n$3\r\nk"[..]);
+        assert!(decoder.decode(&mut buf).unwrap().is_none());
+        // The first argument and the second one's header have been taken
+        // off the buffer already.
+        assert_eq!(&buf[..], b"k");
+        buf.extend_from_slice(b"ey\r\n*1");
+        let (_, consumed) = decoder.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(consumed, 22);
+        assert_eq!(&buf[..], b"*1");
+    }
+
+    #[test]
+    fn bulk_strings_are_binary_safe() {
+        assert_eq!(
+            decode_all(b"*1\r\n$3\r\n\xff\x00\n\r\n", 64).unwrap(),
+            vec![vec![b"\xff\x00\n".to_vec()]]
+        );
+    }
+
+    #[test]
+    fn empty_and_negative_multibulk_lengths_decode_to_empty_requests() {
+        assert_eq!(
+            decode_all(b"*0\r\n*-1\r\n", 64).unwrap(),
+            vec![args(&[]), args(&[])]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_bulk_lengths() {
+        assert_eq!(
+            decode_all(b"*1\r\n$-1\r\n", 64).unwrap_err(),
+            "invalid bulk length"
+        );
+        assert_eq!(
+            decode_all(b"*1\r\n$x\r\n", 64).unwrap_err(),
+            "invalid bulk length"
+        );
+        assert_eq!(
+            decode_all(b"*1\r\n$536870913\r\n", 64).unwrap_err(),
+            "invalid bulk length"
+        );
+        assert_eq!(
+            decode_all(b"*1\r\n$3\r\nabcd\r\n", 64).unwrap_err(),
+            "bulk string not terminated by CRLF"
+        );
+    }
+
+    #[test]
+    fn rejects_oversize_multibulk_lengths() {
+        assert_eq!(
+            decode_all(b"*1048577\r\n", 64).unwrap_err(),
+            "invalid multibulk length"
+        );
+        assert_eq!(
+            decode_all(b"*abc\r\n", 64).unwrap_err(),
+            "invalid multibulk length"
+        );
+        let long_header = [&b"*"[..], &[b'1'; MAX_HEADER_LENGTH + 1]].concat();
+        assert_eq!(
+            decode_all(&long_header, 4096).unwrap_err(),
+            "too big mbulk count string"
+        );
+    }
+
+    #[test]
+    fn rejects_nested_arrays_and_non_bulk_arguments() {
+        assert_eq!(
+            decode_all(b"*1\r\n*1\r\n$4\r\nPING\r\n", 64).unwrap_err(),
+            "expected '$', got '*'"
+        );
+        assert_eq!(
+            decode_all(b"*1\r\n:1\r\n", 64).unwrap_err(),
+            "expected '$', got ':'"
+        );
+    }
+
+    #[test]
+    fn deeply_nested_input_fails_without_recursing() {
+        let input = b"*1\r\n".repeat(300_000);
+        assert_eq!(
+            decode_all(&input, 16 * 1024).unwrap_err(),
+            "expected '$', got '*'"
+        );
+    }
+
+    #[test]
+    fn starts_afresh_after_an_error() {
+        let mut decoder = RequestDecoder::default();
+        let mut buf = BytesMut::from(&b"*2\r\n$3\r\nGET\r\n:1\r\n"[..]);
+        assert!(decoder.decode(&mut buf).is_err());
+        buf.clear();
+        buf.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+        let (request, _) = decoder.decode(&mut buf).unwrap().unwrap();
+        assert!(matches!(request, RESP::Array(args) if args.len() == 1));
+    }
+
+    #[test]
+    fn decodes_inline_commands() {
+        assert_eq!(
+            decode_all(b"SET  foo bar\r\n\nPING\n", 3).unwrap(),
+            vec![args(&["SET", "foo", "bar"]), args(&[]), args(&["PING"])]
+        );
+        let long_line = vec![b'a'; MAX_INLINE_LENGTH + 1];
+        assert_eq!(
+            decode_all(&long_line, 4096).unwrap_err(),
+            "too big inline request"
+        );
+    }
+
+    #[test]
+    fn splits_inline_quotes_and_escapes() {
+        let split = |line: &[u8]| {
+            RESP::split_inline_args(line)
+                .map(|args| args.into_iter().map(|arg| arg.to_vec()).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            split(br#"set "a b" 'c d'"#).unwrap(),
+            args(&["set", "a b", "c d"])
+        );
+        assert_eq!(
+            split(br#""\n\r\t\b\a\x41\"\\" 'it\'s' "\xZZ""#).unwrap(),
+            vec![
+                b"\n\r\t\x08\x07A\"\\".to_vec(),
+                b"it's".to_vec(),
+                b"xZZ".to_vec()
+            ]
+        );
+        assert_eq!(split(br#"'\n'"#).unwrap(), vec![b"\\n".to_vec()]);
+        assert_eq!(split(b"\"abc").unwrap_err(), "unbalanced quotes in request");
+        assert_eq!(split(b"'abc").unwrap_err(), "unbalanced quotes in request");
+        assert_eq!(
+            split(b"\"a\"b").unwrap_err(),
+            "unbalanced quotes in request"
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    blocking::BlockedClients,
+    commands::{CommandTable, Context},
+    config::Config,
+    connection::{ClientClass, Connection, ConnectionEvent},
+    db::Db,
+    error::ServerError,
+    resp::RESP,
+};
+
+const EVENT_QUEUE_SIZE: usize = 1024;
+
+pub struct TcpServer {
+    config: Config,
+    accepted: mpsc::Receiver<TcpStream>,
+    connections: HashMap<usize, Connection>,
+    next_connection_id: usize,
+    commands: CommandTable,
+    db: Db,
+    events: mpsc::Receiver<ConnectionEvent>,
+    events_sender: mpsc::Sender<ConnectionEvent>,
+    blocked: BlockedClients,
+}
+
+impl TcpServer {
+    /// Binds every address in `config.bind`. Addresses prefixed with `-` are
+    /// optional and are skipped if they cannot be bound, as in Redis.
+    pub async fn new(config: Config) -> Result<TcpServer, ServerError> {
+        let (accepted_sender, accepted) = mpsc::channel(EVENT_QUEUE_SIZE);
+        for addr in &config.bind {
+            let (optional, host) = match addr.strip_prefix('-') {
+                Some(host) => (true, host),
+                None => (false, addr.as_str()),
+            };
+
+            match TcpListener::bind((host, config.port)).await {
+                Ok(listener) => {
+                    tokio::spawn(accept_connections(listener, accepted_sender.clone()));
+                }
+                Err(_) if optional => {}
+                Err(source) => {
+                    return Err(ServerError::Bind {
+                        addr: format!("{}:{}", host, config.port),
+                        source,
+                    })
+                }
+            }
+        }
+
+        let (events_sender, events) = mpsc::channel(EVENT_QUEUE_SIZE);
+        Ok(TcpServer {
+            config,
+            accepted,
+            connections: HashMap::new(),
+            next_connection_id: 1,
+            commands: CommandTable::new(),
+            db: Db::default(),
+            events,
+            events_sender,
+            blocked: BlockedClients::default(),
+        })
+    }
+
+    /// Runs the server loop. Socket I/O happens in per-connection tasks, while
+    /// parsing and command execution stay on this single loop, which only
+    /// wakes up when a connection arrives, a reader has something to report,
+    /// a blocked client's timeout runs out, or it is time for the
+    /// `hz`-driven background work in `cron`.
+    /// Errors on one connection only ever close that connection.
+    pub async fn run(&mut self) {
+        let mut cron = tokio::time::interval(self.cron_period());
+        cron.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            let next_timeout = self.blocked.next_deadline();
+            let timeout =
+                tokio::time::sleep_until(next_timeout.unwrap_or_else(Instant::now).into());
+
+            tokio::select! {
+                _ = cron.tick() => self.cron(),
+                _ = timeout, if next_timeout.is_some() => self.time_out_blocked_clients(),
+                Some(stream) = self.accepted.recv() => self.accept_connection(stream),
+                Some(event) = self.events.recv() => match event {
+                    ConnectionEvent::Data(id, data) => self.handle_data(id, &data),
+                    ConnectionEvent::Closed(id) => self.close_connection(id),
+                    ConnectionEvent::Failed(id, e) => {
+                        eprintln!("Error reading from connection {}: {}", id, e);
+                        self.close_connection(id);
+                    }
+                },
+            }
+        }
+    }
+
+    fn cron_period(&self) -> Duration {
+        Duration::from_micros(1_000_000 / self.config.hz as u64)
+    }
+
+    /// Periodic background work. Active expiry may use up to a quarter of
+    /// each period.
+    fn cron(&mut self) {
+        self.db.active_expire_cycle(self.cron_period() / 4);
+    }
+
+    fn accept_connection(&mut self, stream: TcpStream) {
+        let id = self.next_connection_id;
+        self.next_connection_id += 1;
+
+        let connection = Connection::new(
+            id,
+            stream,
+            ClientClass::Normal,
+            &self.config.output_buffer_limits,
+            self.events_sender.clone(),
+        );
+        self.connections.insert(id, connection);
+        println!("New connection {}", id);
+    }
+
+    fn handle_data(&mut self, id: usize, data: &[u8]) {
+        let Some(connection) = self.connections.get_mut(&id) else {
+            return;
+        };
+        connection.buffer.extend_from_slice(data);
+        if !self.blocked.contains(id) {
+            self.process_commands(id);
+        }
+    }
+
+    /// Runs the commands buffered for connection `id`, up to the first one
+    /// that blocks. Whenever a command creates a key that clients are
+    /// blocked on, those clients are served before the next command runs,
+    /// as in Redis.
+    fn process_commands(&mut self, id: usize) {
+        loop {
+            let Some(connection) = self.connections.get_mut(&id) else {
+                return;
+            };
+
+            let mut ctx = Context {
+                commands: &self.commands,
+                config: &self.config,
+                db: &mut self.db,
+                block: None,
+            };
+            if !TcpServer::process_buffer(connection, &mut self.blocked, &mut ctx) {
+                self.close_connection(id);
+                return;
+            }
+
+            if !self.db.has_ready_keys() {
+                return;
+            }
+            self.serve_blocked_clients();
+        }
+    }
+
+    /// Serves the clients blocked on keys that have been created since.
+    fn serve_blocked_clients(&mut self) {
+        let mut ctx = Context {
+            commands: &self.commands,
+            config: &self.config,
+            db: &mut self.db,
+            block: None,
+        };
+        for (id, reply) in self.blocked.serve(&mut ctx) {
+            self.reply_to_unblocked(id, reply);
+        }
+    }
+
+    /// Replies to the blocked clients whose timeout has run out.
+    fn time_out_blocked_clients(&mut self) {
+        for (id, reply) in self.blocked.time_out(&mut self.db, Instant::now()) {
+            self.reply_to_unblocked(id, reply);
+        }
+    }
+
+    /// Sends the reply that ended a client's block, then runs whatever the
+    /// client pipelined after the blocking command.
+    fn reply_to_unblocked(&mut self, id: usize, reply: RESP) {
+        let Some(connection) = self.connections.get_mut(&id) else {
+            return;
+        };
+        let mut replies = Vec::new();
+        reply.write_to(&mut replies);
+        if !TcpServer::send_replies(connection, replies) {
+            self.close_connection(id);
+            return;
+        }
+        self.process_commands(id);
+    }
+
+    fn close_connection(&mut self, id: usize) {
+        let Some(connection) = self.connections.remove(&id) else {
+            return;
+        };
+        self.blocked.unblock(&mut self.db, connection.id);
+        println!("Connection closed: {}", id);
+    }
+
+    /// Runs one command. Returns `None` if there is nothing to reply yet,
+    /// either because the command was empty or because it blocked the
+    /// client.
+    fn execute(
+        connection: &Connection,
+        blocked: &mut BlockedClients,
+        ctx: &mut Context,
+        command: RESP,
+    ) -> Option<RESP> {
+        let RESP::Array(values) = command else {
+            return Some(RESP::Error(
+                "ERR Protocol error: expected a command array".to_string(),
+            ));
+        };
+        if values.is_empty() {
+            return None;
+        }
+
+        let mut args = Vec::with_capacity(values.len());
+        for value in values {
+            match value {
+                RESP::BulkString(arg) => args.push(arg),
+                _ => {
+                    return Some(RESP::Error(
+                        "ERR Protocol error: expected bulk string arguments".to_string(),
+                    ))
+                }
+            }
+        }
+        blocked.execute(ctx, connection.id, args)
+    }
+
+    /// Executes the complete commands in the buffer and queues the replies
+    /// in order, stopping early after a command that blocks or that makes
+    /// keys ready for blocked clients. Returns `false` if the connection
+    /// should be closed, either because the client sent something
+    /// unparseable or because it is not reading its replies fast enough.
+    fn process_buffer(
+        connection: &mut Connection,
+        blocked: &mut BlockedClients,
+        ctx: &mut Context,
+    ) -> bool {
+        let mut replies = Vec::new();
+        let mut keep_open = true;
+
+        while !connection.buffer.is_empty()
+            && !blocked.contains(connection.id)
+            && !ctx.db.has_ready_keys()
+        {
+            let command = match connection.decoder.decode(&mut connection.buffer) {
+                Ok(Some((command, _))) => command,
+                Ok(None) => break,
+                Err(e) => {
+                    RESP::Error(format!("ERR Protocol error: {}", e)).write_to(&mut replies);
+                    connection.buffer.clear();
+                    keep_open = false;
+                    break;
+                }
+            };
+
+            if let Some(response) = TcpServer::execute(connection, blocked, ctx, command) {
+                response.write_to(&mut replies);
+            }
+        }
+
+        TcpServer::send_replies(connection, replies) && keep_open
+    }
+
+    /// Queues encoded replies, returning `false` if that puts the client over
+    /// its output buffer limit.
+    fn send_replies(connection: &mut Connection, replies: Vec<u8>) -> bool {
+        if !replies.is_empty() && !connection.send(replies.into()) {
+            eprintln!(
+                "Client {} is over its output buffer limit, closing it",
+                connection.id
+            );
+            return false;
+        }
+        true
+    }
+}
+
+async fn accept_connections(listener: TcpListener, accepted: mpsc::Sender<TcpStream>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                if accepted.send(stream).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) => eprintln!("Error accepting connection: {}", e),
+        }
+    }
+}
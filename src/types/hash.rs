@@ -0,0 +1,298 @@
+use std::collections::{BTreeSet, HashMap};
+
+use bytes::Bytes;
+
+use super::listpack::Listpack;
+use crate::{dict::Dict, random::random_index};
+
+/// A hash value. Small hashes are a listpack of alternating fields and
+/// values, which is compact but makes every lookup a scan, so a hash moves
+/// to a `Dict` for good once it has more than `max_listpack_entries` fields
+/// or a field or value longer than `max_listpack_value` bytes, following
+/// `hash-max-listpack-entries` and `hash-max-listpack-value`.
+///
+/// Fields can be given their own expiry time. Expired fields are not
+/// filtered out here; the keyspace reclaims them through
+/// `remove_expired_fields` before handing the hash out.
+pub struct Hash {
+    encoding: Encoding,
+    expiries: FieldExpiries,
+    max_listpack_entries: usize,
+    max_listpack_value: usize,
+}
+
+/// The expiry times (unix milliseconds) of the fields that have one, also
+/// ordered by time so that the next field due is cheap to find.
+#[derive(Default)]
+struct FieldExpiries {
+    by_field: HashMap<Bytes, u64>,
+    by_time: BTreeSet<(u64, Bytes)>,
+}
+
+impl FieldExpiries {
+    fn set(&mut self, field: &[u8], when: u64) {
+        self.remove(field);
+        let field = Bytes::copy_from_slice(field);
+        self.by_time.insert((when, field.clone()));
+        self.by_field.insert(field, when);
+    }
+
+    fn remove(&mut self, field: &[u8]) -> Option<u64> {
+        if self.by_field.is_empty() {
+            return None;
+        }
+        let (field, when) = self.by_field.remove_entry(field)?;
+        self.by_time.remove(&(when, field));
+        Some(when)
+    }
+
+    /// Takes the field that expires first if its time is at or before `now`.
+    fn pop_expired(&mut self, now: u64) -> Option<Bytes> {
+        let (when, _) = self.by_time.first()?;
+        if *when > now {
+            return None;
+        }
+        let (_, field) = self.by_time.pop_first()?;
+        self.by_field.remove(&field);
+        Some(field)
+    }
+}
+
+enum Encoding {
+    Listpack(Listpack),
+    Table(Dict<Bytes, Bytes>),
+}
+
+impl Hash {
+    pub fn new(max_listpack_entries: usize, max_listpack_value: usize) -> Hash {
+        Hash {
+            encoding: Encoding::Listpack(Listpack::default()),
+            expiries: FieldExpiries::default(),
+            max_listpack_entries,
+            max_listpack_value,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => listpack.len() / 2,
+            Encoding::Table(table) => table.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&[u8]> {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => listpack
+                .pairs()
+                .find(|(candidate, _)| *candidate == field)
+                .map(|(_, value)| value),
+            Encoding::Table(table) => table.get(field).map(|value| &value[..]),
+        }
+    }
+
+    pub fn contains(&self, field: &[u8]) -> bool {
+        self.get(field).is_some()
+    }
+
+    /// Sets `field` to `value`, returning whether the field is new. Any
+    /// expiry on the field is cleared unless `keep_ttl` is set.
+    pub fn insert(&mut self, field: &[u8], value: &[u8], keep_ttl: bool) -> bool {
+        if !keep_ttl {
+            self.expiries.remove(field);
+        }
+        if field.len() > self.max_listpack_value || value.len() > self.max_listpack_value {
+            self.convert_to_table();
+        }
+
+        if let Encoding::Listpack(listpack) = &mut self.encoding {
+            let index = listpack
+                .pairs()
+                .position(|(candidate, _)| candidate == field);
+            if let Some(index) = index {
+                listpack.replace(index * 2 + 1, value);
+                return false;
+            }
+            if listpack.len() / 2 < self.max_listpack_entries {
+                listpack.push_back(field);
+                listpack.push_back(value);
+                return true;
+            }
+            self.convert_to_table();
+        }
+
+        let Encoding::Table(table) = &mut self.encoding else {
+            unreachable!("listpack hashes are handled above");
+        };
+        table
+            .insert(Bytes::copy_from_slice(field), Bytes::copy_from_slice(value))
+            .is_none()
+    }
+
+    /// Removes `field`, returning whether it was there.
+    pub fn remove(&mut self, field: &[u8]) -> bool {
+        self.expiries.remove(field);
+        match &mut self.encoding {
+            Encoding::Listpack(listpack) => {
+                let Some(index) = listpack
+                    .pairs()
+                    .position(|(candidate, _)| candidate == field)
+                else {
+                    return false;
+                };
+                listpack.remove_range(index * 2, 2);
+                true
+            }
+            Encoding::Table(table) => table.remove(field).is_some(),
+        }
+    }
+
+    /// The expiry time of `field` in unix milliseconds, if it has one.
+    pub fn field_expiry(&self, field: &[u8]) -> Option<u64> {
+        self.expiries.by_field.get(field).copied()
+    }
+
+    /// Sets the expiry time of `field`, which must exist.
+    pub fn set_field_expiry(&mut self, field: &[u8], when: u64) {
+        self.expiries.set(field, when);
+    }
+
+    /// Removes the expiry of `field`, returning whether it had one.
+    pub fn persist_field(&mut self, field: &[u8]) -> bool {
+        self.expiries.remove(field).is_some()
+    }
+
+    pub fn has_field_expiries(&self) -> bool {
+        !self.expiries.by_field.is_empty()
+    }
+
+    /// Removes the fields whose expiry time is at or before `now`,
+    /// returning how many there were.
+    pub fn remove_expired_fields(&mut self, now: u64) -> usize {
+        let mut removed = 0;
+        while let Some(field) = self.expiries.pop_expired(now) {
+            self.remove(&field);
+            removed += 1;
+        }
+        removed
+    }
+
+    fn convert_to_table(&mut self) {
+        let Encoding::Listpack(listpack) = &self.encoding else {
+            return;
+        };
+        let mut table = Dict::default();
+        for (field, value) in listpack.pairs() {
+            table.insert(Bytes::copy_from_slice(field), Bytes::copy_from_slice(value));
+        }
+        self.encoding = Encoding::Table(table);
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&[u8], &[u8])> + '_> {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => Box::new(listpack.pairs()),
+            Encoding::Table(table) => {
+                Box::new(table.iter().map(|(field, value)| (&field[..], &value[..])))
+            }
+        }
+    }
+
+    pub fn random_entry(&self) -> Option<(&[u8], &[u8])> {
+        match &self.encoding {
+            Encoding::Listpack(listpack) if listpack.is_empty() => None,
+            Encoding::Listpack(listpack) => {
+                let index = random_index(listpack.len() / 2) * 2;
+                Some((listpack.get(index)?, listpack.get(index + 1)?))
+            }
+            Encoding::Table(table) => table
+                .random_entry()
+                .map(|(field, value)| (&field[..], &value[..])),
+        }
+    }
+
+    /// Scans the fields as [`Dict::scan`] does. A listpack hash is small
+    /// enough to be visited in one go.
+    pub fn scan(&self, cursor: u64, count: usize, mut visit: impl FnMut(&[u8], &[u8])) -> u64 {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => {
+                listpack
+                    .pairs()
+                    .for_each(|(field, value)| visit(field, value));
+                0
+            }
+            Encoding::Table(table) => table.scan(cursor, count, |field, value| visit(field, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that both views of `expiries` hold exactly `expected`.
+    fn check(expiries: &FieldExpiries, expected: &[(&str, u64)]) {
+        let mut by_field: Vec<_> = expiries
+            .by_field
+            .iter()
+            .map(|(field, &when)| (when, field.clone()))
+            .collect();
+        by_field.sort();
+        let by_time: Vec<_> = expiries.by_time.iter().cloned().collect();
+        let mut expected: Vec<_> = expected
+            .iter()
+            .map(|&(field, when)| (when, Bytes::copy_from_slice(field.as_bytes())))
+            .collect();
+        expected.sort();
+        assert_eq!(by_field, expected);
+        assert_eq!(by_time, expected);
+    }
+
+    #[test]
+    fn field_expiries_stay_in_step() {
+        let mut expiries = FieldExpiries::default();
+        expiries.set(b"a", 30);
+        expiries.set(b"b", 10);
+        expiries.set(b"c", 30);
+        check(&expiries, &[("a", 30), ("b", 10), ("c", 30)]);
+
+        // Moving a field's time drops its old place in the ordering.
+        expiries.set(b"a", 5);
+        check(&expiries, &[("a", 5), ("b", 10), ("c", 30)]);
+        assert_eq!(expiries.remove(b"b"), Some(10));
+        assert_eq!(expiries.remove(b"b"), None);
+        check(&expiries, &[("a", 5), ("c", 30)]);
+
+        assert_eq!(expiries.pop_expired(4), None);
+        assert_eq!(expiries.pop_expired(30).as_deref(), Some(&b"a"[..]));
+        assert_eq!(expiries.pop_expired(30).as_deref(), Some(&b"c"[..]));
+        assert_eq!(expiries.pop_expired(30), None);
+        check(&expiries, &[]);
+    }
+
+    #[test]
+    fn removing_or_overwriting_a_field_clears_its_expiry() {
+        let mut hash = Hash::new(2, 64);
+        hash.insert(b"a", b"1", false);
+        hash.insert(b"b", b"2", false);
+        hash.set_field_expiry(b"a", 10);
+        hash.set_field_expiry(b"b", 20);
+
+        hash.insert(b"a", b"3", true);
+        assert_eq!(hash.field_expiry(b"a"), Some(10));
+        hash.insert(b"a", b"4", false);
+        assert_eq!(hash.field_expiry(b"a"), None);
+
+        // Past the listpack limit, so the fields move to a table.
+        hash.insert(b"c", b"5", false);
+        hash.set_field_expiry(b"c", 20);
+        assert!(hash.remove(b"b"));
+        check(&hash.expiries, &[("c", 20)]);
+
+        assert_eq!(hash.remove_expired_fields(20), 1);
+        assert_eq!(hash.len(), 1);
+        assert!(!hash.has_field_expiries());
+    }
+}
@@ -0,0 +1,179 @@
+/// A sorted set of integers packed into a single buffer, like Redis'
+/// intset. Every element is stored little-endian at the same width of 2, 4
+/// or 8 bytes, the smallest that fits all of them; adding a value that needs
+/// more re-encodes the whole set at the wider width.
+///
+/// Lookups are a binary search, but inserts and removals shift the elements
+/// after them, so an intset is only meant to hold a bounded number of
+/// elements.
+pub struct Intset {
+    data: Vec<u8>,
+    width: usize,
+}
+
+impl Default for Intset {
+    fn default() -> Intset {
+        Intset {
+            data: Vec::new(),
+            width: 2,
+        }
+    }
+}
+
+/// The width `value` needs.
+fn width_for(value: i64) -> usize {
+    if i16::try_from(value).is_ok() {
+        2
+    } else if i32::try_from(value).is_ok() {
+        4
+    } else {
+        8
+    }
+}
+
+/// Decodes an element stored at the width of `bytes`.
+fn decode(bytes: &[u8]) -> i64 {
+    match *bytes {
+        [a, b] => i16::from_le_bytes([a, b]) as i64,
+        [a, b, c, d] => i32::from_le_bytes([a, b, c, d]) as i64,
+        _ => i64::from_le_bytes(bytes.try_into().unwrap_or_default()),
+    }
+}
+
+/// Encodes `value` at `width` bytes, which must fit it.
+fn encode(value: i64, width: usize) -> Vec<u8> {
+    value.to_le_bytes()[..width].to_vec()
+}
+
+impl Intset {
+    pub fn len(&self) -> usize {
+        self.data.len() / self.width
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<i64> {
+        let start = index.checked_mul(self.width)?;
+        self.data.get(start..start + self.width).map(decode)
+    }
+
+    /// Binary searches for `value`, as `slice::binary_search` does.
+    fn search(&self, value: i64) -> Result<usize, usize> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.get(mid).map(|element| element.cmp(&value)) {
+                Some(std::cmp::Ordering::Less) => low = mid + 1,
+                Some(std::cmp::Ordering::Greater) => high = mid,
+                _ => return Ok(mid),
+            }
+        }
+        Err(low)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        self.search(value).is_ok()
+    }
+
+    /// Adds `value`, returning whether it was not there yet.
+    pub fn insert(&mut self, value: i64) -> bool {
+        let width = width_for(value);
+        if width > self.width {
+            self.data = self
+                .iter()
+                .flat_map(|element| encode(element, width))
+                .collect();
+            self.width = width;
+        }
+        let Err(index) = self.search(value) else {
+            return false;
+        };
+        let pos = index * self.width;
+        self.data.splice(pos..pos, encode(value, self.width));
+        true
+    }
+
+    /// Removes `value`, returning whether it was there.
+    pub fn remove(&mut self, value: i64) -> bool {
+        let Ok(index) = self.search(value) else {
+            return false;
+        };
+        let pos = index * self.width;
+        self.data.drain(pos..pos + self.width);
+        true
+    }
+
+    /// Iterates over the elements in ascending order.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = i64> + ExactSizeIterator + '_ {
+        self.data.chunks_exact(self.width).map(decode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that `set` holds exactly `expected`, ascending, at `width`.
+    fn check(set: &Intset, expected: &[i64], width: usize) {
+        assert_eq!(set.width, width);
+        assert_eq!(set.len(), expected.len());
+        assert!(set.iter().eq(expected.iter().copied()));
+        assert!(set.iter().rev().eq(expected.iter().rev().copied()));
+        for (index, &value) in expected.iter().enumerate() {
+            assert_eq!(set.get(index), Some(value));
+            assert!(set.contains(value));
+        }
+        assert_eq!(set.get(expected.len()), None);
+    }
+
+    #[test]
+    fn widens_from_16_to_32_to_64_bits() {
+        let mut set = Intset::default();
+        for value in [5, i16::MIN as i64, -7, i16::MAX as i64] {
+            assert!(set.insert(value));
+        }
+        check(&set, &[i16::MIN as i64, -7, 5, i16::MAX as i64], 2);
+
+        assert!(set.insert(i16::MAX as i64 + 1));
+        let mut expected = vec![i16::MIN as i64, -7, 5, i16::MAX as i64, i16::MAX as i64 + 1];
+        check(&set, &expected, 4);
+
+        assert!(set.insert(i32::MIN as i64));
+        assert!(set.insert(i32::MAX as i64));
+        expected.insert(0, i32::MIN as i64);
+        expected.push(i32::MAX as i64);
+        check(&set, &expected, 4);
+
+        // A value that only fits 64 bits going in at the front.
+        assert!(set.insert(i64::MIN));
+        expected.insert(0, i64::MIN);
+        check(&set, &expected, 8);
+
+        assert!(set.insert(i64::MAX));
+        assert!(!set.insert(i64::MIN));
+        assert!(!set.insert(-7));
+        expected.push(i64::MAX);
+        check(&set, &expected, 8);
+
+        // Removing the wide values keeps the width, as in Redis.
+        assert!(set.remove(i64::MIN));
+        assert!(set.remove(i64::MAX));
+        assert!(!set.remove(i64::MIN));
+        expected.remove(0);
+        expected.pop();
+        check(&set, &expected, 8);
+    }
+
+    #[test]
+    fn widens_straight_to_64_bits() {
+        let mut set = Intset::default();
+        set.insert(1);
+        set.insert(-1);
+        set.insert(i64::MIN);
+        check(&set, &[i64::MIN, -1, 1], 8);
+        assert!(!set.contains(0));
+        assert!(!set.contains(i64::MAX));
+    }
+}
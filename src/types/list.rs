@@ -0,0 +1,383 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use super::listpack::Listpack;
+
+/// Nodes filled by entry count are still capped at this many bytes, as in
+/// Redis' quicklist.
+const SIZE_SAFETY_LIMIT: usize = 8192;
+
+/// A list value, kept as a deque of listpack nodes like Redis' quicklist.
+/// Pushes and pops at either end only touch the node at that end, while
+/// every node packs many entries into one allocation.
+///
+/// `fill` is `list-max-listpack-size`: a positive value caps the number of
+/// entries per node, while -1 to -5 cap each node at 4, 8, 16, 32 or 64KB.
+/// An entry too big for any node gets a node of its own.
+pub struct List {
+    nodes: VecDeque<Listpack>,
+    len: usize,
+    fill: i64,
+}
+
+impl List {
+    pub fn new(fill: i64) -> List {
+        List {
+            nodes: VecDeque::new(),
+            len: 0,
+            fill,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether a node with this many entries and bytes respects `fill`.
+    fn within_limits(&self, entries: usize, bytes: usize) -> bool {
+        if self.fill >= 0 {
+            entries <= self.fill as usize && bytes <= SIZE_SAFETY_LIMIT
+        } else {
+            bytes <= 4096 << ((-self.fill).min(5) - 1)
+        }
+    }
+
+    /// Whether `node` can take one more entry holding `value`.
+    fn fits(&self, node: &Listpack, value: &[u8]) -> bool {
+        node.is_empty()
+            || self.within_limits(node.len() + 1, node.bytes() + Listpack::entry_size(value))
+    }
+
+    pub fn push_front(&mut self, value: &[u8]) {
+        match self.nodes.front() {
+            Some(node) if self.fits(node, value) => {}
+            _ => self.nodes.push_front(Listpack::default()),
+        }
+        if let Some(node) = self.nodes.front_mut() {
+            node.push_front(value);
+        }
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, value: &[u8]) {
+        match self.nodes.back() {
+            Some(node) if self.fits(node, value) => {}
+            _ => self.nodes.push_back(Listpack::default()),
+        }
+        if let Some(node) = self.nodes.back_mut() {
+            node.push_back(value);
+        }
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<Bytes> {
+        let node = self.nodes.front_mut()?;
+        let value = node.pop_front();
+        if node.is_empty() {
+            self.nodes.pop_front();
+        }
+        self.len -= 1;
+        value
+    }
+
+    pub fn pop_back(&mut self) -> Option<Bytes> {
+        let node = self.nodes.back_mut()?;
+        let value = node.pop_back();
+        if node.is_empty() {
+            self.nodes.pop_back();
+        }
+        self.len -= 1;
+        value
+    }
+
+    /// Pushes `value` onto the head of the list if `front` is set, and onto
+    /// the tail otherwise.
+    pub fn push(&mut self, value: &[u8], front: bool) {
+        if front {
+            self.push_front(value);
+        } else {
+            self.push_back(value);
+        }
+    }
+
+    pub fn pop(&mut self, front: bool) -> Option<Bytes> {
+        if front {
+            self.pop_front()
+        } else {
+            self.pop_back()
+        }
+    }
+
+    /// The node holding entry `index` and the entry's index inside it,
+    /// counting from whichever end of the list is closer.
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        if index < self.len / 2 {
+            let mut index = index;
+            for (i, node) in self.nodes.iter().enumerate() {
+                if index < node.len() {
+                    return Some((i, index));
+                }
+                index -= node.len();
+            }
+        } else {
+            let mut from_back = self.len - index;
+            for (i, node) in self.nodes.iter().enumerate().rev() {
+                if from_back <= node.len() {
+                    return Some((i, node.len() - from_back));
+                }
+                from_back -= node.len();
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        let (node, offset) = self.locate(index)?;
+        self.nodes[node].get(offset)
+    }
+
+    /// Replaces entry `index`, returning `false` if there is no such entry.
+    pub fn set(&mut self, index: usize, value: &[u8]) -> bool {
+        let Some((node, offset)) = self.locate(index) else {
+            return false;
+        };
+        self.nodes[node].replace(offset, value);
+        self.split_if_oversized(node);
+        true
+    }
+
+    /// Inserts `value` so that it becomes entry `index`, which may be `len`.
+    pub fn insert(&mut self, index: usize, value: &[u8]) {
+        let Some((node, offset)) = self.locate(index) else {
+            self.push_back(value);
+            return;
+        };
+        self.nodes[node].insert(offset, value);
+        self.len += 1;
+        self.split_if_oversized(node);
+    }
+
+    /// Splits `node` in halves, and those halves again, for as long as an
+    /// in-place edit left them over the fill limit.
+    fn split_if_oversized(&mut self, node: usize) {
+        let entries = self.nodes[node].len();
+        if entries > 1 && !self.within_limits(entries, self.nodes[node].bytes()) {
+            let tail = self.nodes[node].split_off(entries / 2);
+            self.nodes.insert(node + 1, tail);
+            self.split_if_oversized(node + 1);
+            self.split_if_oversized(node);
+        }
+    }
+
+    /// Iterates over the list from entry `index` onwards. The iterator can
+    /// also be walked from the back.
+    pub fn iter_from(&self, index: usize) -> impl DoubleEndedIterator<Item = &[u8]> + '_ {
+        let (node, offset) = self.locate(index).unwrap_or((self.nodes.len(), 0));
+        let first = self.nodes.get(node).map(|first| first.iter_from(offset));
+        first
+            .into_iter()
+            .flatten()
+            .chain(self.nodes.iter().skip(node + 1).flat_map(Listpack::iter))
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &[u8]> + '_ {
+        self.nodes.iter().flat_map(Listpack::iter)
+    }
+
+    /// Removes `count` entries starting at entry `index`.
+    pub fn remove_range(&mut self, index: usize, count: usize) {
+        let mut count = count.min(self.len.saturating_sub(index));
+        let Some((mut node, mut offset)) = self.locate(index) else {
+            return;
+        };
+        self.len -= count;
+        while count > 0 {
+            let removed = count.min(self.nodes[node].len() - offset);
+            if removed == self.nodes[node].len() {
+                self.nodes.remove(node);
+            } else {
+                self.nodes[node].remove_range(offset, removed);
+                node += 1;
+            }
+            count -= removed;
+            offset = 0;
+        }
+    }
+
+    /// Removes entries equal to `value`: the first `count` of them if
+    /// `count` is positive, the last `-count` if it is negative and all of
+    /// them if it is zero. Returns how many were removed.
+    pub fn remove_matching(&mut self, value: &[u8], count: i64) -> usize {
+        let limit = match count {
+            0 => usize::MAX,
+            count => count.unsigned_abs() as usize,
+        };
+        let mut removed = 0;
+        let order: Vec<usize> = if count < 0 {
+            (0..self.nodes.len()).rev().collect()
+        } else {
+            (0..self.nodes.len()).collect()
+        };
+
+        for i in order {
+            if removed == limit {
+                break;
+            }
+            let node = &mut self.nodes[i];
+            let matches = node.iter().filter(|entry| *entry == value).count();
+            if matches == 0 {
+                continue;
+            }
+
+            // Only the matches nearest the end we started from are removed
+            // once the limit is in sight.
+            let take = matches.min(limit - removed);
+            let mut skip = if count < 0 { matches - take } else { 0 };
+            let mut left = take;
+            node.retain(|entry| {
+                if entry != value || left == 0 {
+                    return true;
+                }
+                if skip > 0 {
+                    skip -= 1;
+                    return true;
+                }
+                left -= 1;
+                false
+            });
+            removed += take;
+        }
+
+        self.nodes.retain(|node| !node.is_empty());
+        self.len -= removed;
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::random_index;
+
+    /// Checks the list against `model` and that every node is non-empty and
+    /// within the fill limit, unless it holds a single oversized entry.
+    fn check(list: &List, model: &VecDeque<Vec<u8>>) {
+        assert_eq!(list.len(), model.len());
+        assert_eq!(
+            list.nodes.iter().map(Listpack::len).sum::<usize>(),
+            model.len()
+        );
+        for node in &list.nodes {
+            assert!(!node.is_empty());
+            assert!(node.len() == 1 || list.within_limits(node.len(), node.bytes()));
+        }
+
+        assert!(list.iter().eq(model.iter().map(Vec::as_slice)));
+        assert!(list.iter().rev().eq(model.iter().rev().map(Vec::as_slice)));
+        for index in 0..=model.len() {
+            let forward: Vec<_> = list.iter_from(index).collect();
+            let expected: Vec<_> = model.iter().skip(index).map(Vec::as_slice).collect();
+            assert_eq!(forward, expected, "{index}");
+            assert_eq!(list.get(index), model.get(index).map(Vec::as_slice));
+        }
+    }
+
+    /// Applies random edits to `list`, mirroring them on a deque.
+    fn random_edits(mut list: List, max_len: usize) {
+        let mut model: VecDeque<Vec<u8>> = VecDeque::new();
+        for step in 0..3000 {
+            let entry = vec![b'a' + random_index(3) as u8; random_index(max_len)];
+            match random_index(8) {
+                0 => {
+                    list.push_front(&entry);
+                    model.push_front(entry);
+                }
+                1 => {
+                    list.push_back(&entry);
+                    model.push_back(entry);
+                }
+                2 | 3 => {
+                    let index = random_index(model.len() + 1);
+                    list.insert(index, &entry);
+                    model.insert(index, entry);
+                }
+                4 => {
+                    let index = random_index(model.len() + 1);
+                    assert_eq!(list.set(index, &entry), index < model.len());
+                    if let Some(old) = model.get_mut(index) {
+                        *old = entry;
+                    }
+                }
+                5 => {
+                    let index = random_index(model.len() + 1);
+                    let count = random_index(12);
+                    list.remove_range(index, count);
+                    let end = (index + count).min(model.len());
+                    model.drain(index..end);
+                }
+                6 => {
+                    let count = random_index(5) as i64 - 2;
+                    let limit = if count == 0 {
+                        usize::MAX
+                    } else {
+                        count.unsigned_abs() as usize
+                    };
+                    let mut matching: Vec<usize> =
+                        (0..model.len()).filter(|&i| model[i] == entry).collect();
+                    if count < 0 {
+                        matching.reverse();
+                    }
+                    matching.truncate(limit);
+                    matching.sort_unstable();
+                    for &index in matching.iter().rev() {
+                        model.remove(index);
+                    }
+                    assert_eq!(list.remove_matching(&entry, count), matching.len());
+                }
+                _ => {
+                    let front = random_index(2) == 0;
+                    let expected = if front {
+                        model.pop_front()
+                    } else {
+                        model.pop_back()
+                    };
+                    assert_eq!(list.pop(front), expected.map(Bytes::from));
+                }
+            }
+            if step % 100 == 0 {
+                check(&list, &model);
+            }
+        }
+        check(&list, &model);
+    }
+
+    #[test]
+    fn random_edits_match_a_deque_with_an_entry_limit() {
+        random_edits(List::new(4), 4);
+    }
+
+    #[test]
+    fn random_edits_match_a_deque_with_a_size_limit() {
+        // Entries of up to 2KB against 4KB nodes, so some nodes hold a
+        // single entry and in-place edits have to split.
+        random_edits(List::new(-1), 2048);
+    }
+
+    #[test]
+    fn oversized_entries_get_a_node_of_their_own() {
+        let mut list = List::new(-1);
+        list.push_back(b"small");
+        list.push_back(&[0; 5000]);
+        list.push_back(b"small");
+        assert_eq!(list.nodes.len(), 3);
+        assert_eq!(list.get(1), Some(&[0; 5000][..]));
+    }
+}
@@ -0,0 +1,415 @@
+use bytes::Bytes;
+
+/// A sequence of byte strings packed back to back into a single buffer, in
+/// the spirit of Redis' listpack. Each entry is stored as
+///
+/// ```text
+/// <length> <bytes> <back-length>
+/// ```
+///
+/// where `length` is the size of `bytes` as a LEB128 varint and
+/// `back-length` is the size of `length` plus `bytes`, with its varint bytes
+/// in reverse order so it can be read from the entry's last byte. That makes
+/// the buffer walkable from both ends while costing two or three bytes per
+/// entry for short strings, instead of a pointer and an allocation each.
+///
+/// Lookups by index walk the buffer, so a listpack is only meant to hold a
+/// bounded amount of data.
+#[derive(Clone, Default)]
+pub struct Listpack {
+    data: Vec<u8>,
+    len: usize,
+}
+
+/// Appends `value` to `out` as a LEB128 varint.
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a LEB128 varint starting at `pos`, returning it and its size.
+fn read_varint(data: &[u8], pos: usize) -> (usize, usize) {
+    let mut value = 0;
+    let mut size = 0;
+    loop {
+        let byte = data[pos + size];
+        value |= ((byte & 0x7f) as usize) << (7 * size);
+        size += 1;
+        if byte & 0x80 == 0 {
+            return (value, size);
+        }
+    }
+}
+
+/// Reads a back-length ending just before `end`, returning it and its size.
+fn read_back_varint(data: &[u8], end: usize) -> (usize, usize) {
+    let mut value = 0;
+    let mut size = 0;
+    loop {
+        let byte = data[end - 1 - size];
+        value |= ((byte & 0x7f) as usize) << (7 * size);
+        size += 1;
+        if byte & 0x80 == 0 {
+            return (value, size);
+        }
+    }
+}
+
+/// The encoded form of an entry holding `value`.
+fn encode(value: &[u8]) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(value.len() + 6);
+    write_varint(&mut entry, value.len());
+    entry.extend_from_slice(value);
+    let mut back = Vec::with_capacity(3);
+    write_varint(&mut back, entry.len());
+    entry.extend(back.iter().rev());
+    entry
+}
+
+impl Listpack {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bytes the entries take up.
+    pub fn bytes(&self) -> usize {
+        self.data.len()
+    }
+
+    /// How many bytes an entry holding `value` takes up.
+    pub fn entry_size(value: &[u8]) -> usize {
+        let size = varint_size(value.len()) + value.len();
+        size + varint_size(size)
+    }
+
+    /// The value of the entry starting at byte `pos` and the position of the
+    /// entry after it.
+    fn entry_at(&self, pos: usize) -> (&[u8], usize) {
+        let (length, size) = read_varint(&self.data, pos);
+        let start = pos + size;
+        let end = start + length;
+        (&self.data[start..end], end + varint_size(size + length))
+    }
+
+    /// The byte position of entry `index`, or of the end of the buffer when
+    /// `index` is `len`. Walks from whichever end is closer.
+    fn position(&self, index: usize) -> usize {
+        if index <= self.len / 2 {
+            let mut pos = 0;
+            for _ in 0..index {
+                pos = self.entry_at(pos).1;
+            }
+            pos
+        } else {
+            let mut pos = self.data.len();
+            for _ in index..self.len {
+                let (back, size) = read_back_varint(&self.data, pos);
+                pos -= back + size;
+            }
+            pos
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&[u8]> {
+        (index < self.len).then(|| self.entry_at(self.position(index)).0)
+    }
+
+    /// Iterates over the entries from `index` onwards.
+    pub fn iter_from(&self, index: usize) -> Iter<'_> {
+        Iter {
+            data: &self.data,
+            front: self.position(index.min(self.len)),
+            back: self.data.len(),
+            remaining: self.len.saturating_sub(index),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        self.iter_from(0)
+    }
+
+    /// Iterates over the entries two at a time, for listpacks holding pairs
+    /// such as fields and values.
+    pub fn pairs(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        let mut entries = self.iter();
+        std::iter::from_fn(move || Some((entries.next()?, entries.next()?)))
+    }
+
+    /// Inserts `value` so that it becomes entry `index`.
+    pub fn insert(&mut self, index: usize, value: &[u8]) {
+        let pos = self.position(index);
+        self.data.splice(pos..pos, encode(value));
+        self.len += 1;
+    }
+
+    pub fn push_front(&mut self, value: &[u8]) {
+        self.insert(0, value);
+    }
+
+    pub fn push_back(&mut self, value: &[u8]) {
+        self.data.extend_from_slice(&encode(value));
+        self.len += 1;
+    }
+
+    /// Replaces the value of entry `index`, which must exist.
+    pub fn replace(&mut self, index: usize, value: &[u8]) {
+        let pos = self.position(index);
+        let next = self.entry_at(pos).1;
+        self.data.splice(pos..next, encode(value));
+    }
+
+    /// Removes `count` entries starting at entry `index`.
+    pub fn remove_range(&mut self, index: usize, count: usize) {
+        let count = count.min(self.len.saturating_sub(index));
+        if count == 0 {
+            return;
+        }
+        let start = self.position(index);
+        let mut end = start;
+        for _ in 0..count {
+            end = self.entry_at(end).1;
+        }
+        self.data.drain(start..end);
+        self.len -= count;
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Bytes> {
+        let value = Bytes::copy_from_slice(self.get(index)?);
+        self.remove_range(index, 1);
+        Some(value)
+    }
+
+    pub fn pop_front(&mut self) -> Option<Bytes> {
+        self.remove(0)
+    }
+
+    pub fn pop_back(&mut self) -> Option<Bytes> {
+        self.remove(self.len.checked_sub(1)?)
+    }
+
+    /// Keeps only the entries for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&[u8]) -> bool) {
+        let mut kept = Listpack::default();
+        for value in self.iter() {
+            if keep(value) {
+                kept.push_back(value);
+            }
+        }
+        *self = kept;
+    }
+
+    /// Splits the listpack in two, returning the entries from `index`
+    /// onwards.
+    pub fn split_off(&mut self, index: usize) -> Listpack {
+        let index = index.min(self.len);
+        let pos = self.position(index);
+        let tail = Listpack {
+            data: self.data.split_off(pos),
+            len: self.len - index,
+        };
+        self.len = index;
+        tail
+    }
+}
+
+/// How many bytes `value` takes up as a varint.
+fn varint_size(mut value: usize) -> usize {
+    let mut size = 1;
+    while value >= 0x80 {
+        size += 1;
+        value >>= 7;
+    }
+    size
+}
+
+pub struct Iter<'a> {
+    data: &'a [u8],
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (length, size) = read_varint(self.data, self.front);
+        let start = self.front + size;
+        self.front = start + length + varint_size(size + length);
+        self.remaining -= 1;
+        Some(&self.data[start..start + length])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<&'a [u8]> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let (back, back_size) = read_back_varint(self.data, self.back);
+        let pos = self.back - back_size - back;
+        let (length, size) = read_varint(self.data, pos);
+        self.back = pos;
+        self.remaining -= 1;
+        Some(&self.data[pos + size..pos + size + length])
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+    use crate::random::random_index;
+
+    /// Checks the listpack against `model` from both ends and by index.
+    fn check(listpack: &Listpack, model: &VecDeque<Vec<u8>>) {
+        assert_eq!(listpack.len(), model.len());
+        assert!(listpack.iter().eq(model.iter().map(Vec::as_slice)));
+        assert!(listpack
+            .iter()
+            .rev()
+            .eq(model.iter().rev().map(Vec::as_slice)));
+        for (index, value) in model.iter().enumerate() {
+            assert_eq!(listpack.get(index), Some(value.as_slice()));
+        }
+        assert_eq!(listpack.get(model.len()), None);
+    }
+
+    /// A value of `len` bytes, so that entries of different sizes can be
+    /// told apart.
+    fn value(len: usize, tag: u8) -> Vec<u8> {
+        vec![tag; len]
+    }
+
+    #[test]
+    fn varints_round_trip_at_the_7_bit_boundaries() {
+        for value in [
+            0,
+            1,
+            0x7f,
+            0x80,
+            0x3fff,
+            0x4000,
+            0x1f_ffff,
+            0x20_0000,
+            usize::MAX,
+        ] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            assert_eq!(out.len(), varint_size(value), "{value:#x}");
+            assert_eq!(read_varint(&out, 0), (value, out.len()), "{value:#x}");
+
+            out.reverse();
+            assert_eq!(
+                read_back_varint(&out, out.len()),
+                (value, out.len()),
+                "{value:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn entry_size_matches_the_encoding() {
+        for len in [0, 1, 0x7d, 0x7e, 0x7f, 0x80, 0x3ffd, 0x3ffe, 0x4000, 70_000] {
+            assert_eq!(
+                Listpack::entry_size(&value(len, 0)),
+                encode(&value(len, 0)).len(),
+                "{len}"
+            );
+        }
+    }
+
+    #[test]
+    fn walks_backwards_over_multi_byte_entries() {
+        // Lengths either side of where the length and back-length varints
+        // grow to two and three bytes.
+        let lengths = [
+            0, 0x7d, 0x7e, 0x7f, 0x80, 5, 0x3ffd, 0x3ffe, 0x4000, 1, 70_000,
+        ];
+        let mut listpack = Listpack::default();
+        let mut model = VecDeque::new();
+        for (tag, &len) in lengths.iter().enumerate() {
+            listpack.push_back(&value(len, tag as u8));
+            model.push_back(value(len, tag as u8));
+        }
+        check(&listpack, &model);
+
+        for index in 0..=model.len() {
+            let backward: Vec<_> = listpack.iter_from(index).rev().collect();
+            let expected: Vec<_> = model.iter().skip(index).rev().map(Vec::as_slice).collect();
+            assert_eq!(backward, expected, "{index}");
+        }
+    }
+
+    #[test]
+    fn random_edits_match_a_deque() {
+        let mut listpack = Listpack::default();
+        let mut model: VecDeque<Vec<u8>> = VecDeque::new();
+        for step in 0..3000 {
+            // Mostly short entries, with the odd one needing longer varints.
+            let len = match random_index(10) {
+                0 => 0x7e + random_index(200),
+                _ => random_index(20),
+            };
+            let entry = value(len, step as u8);
+            match random_index(6) {
+                0 => {
+                    listpack.push_front(&entry);
+                    model.push_front(entry);
+                }
+                1 => {
+                    listpack.push_back(&entry);
+                    model.push_back(entry);
+                }
+                2 => {
+                    let index = random_index(model.len() + 1);
+                    listpack.insert(index, &entry);
+                    model.insert(index, entry);
+                }
+                3 if !model.is_empty() => {
+                    let index = random_index(model.len());
+                    listpack.replace(index, &entry);
+                    model[index] = entry;
+                }
+                4 => {
+                    let index = random_index(model.len() + 1);
+                    let count = random_index(4);
+                    listpack.remove_range(index, count);
+                    let end = (index + count).min(model.len());
+                    model.drain(index..end);
+                }
+                _ => {
+                    let expected = model.pop_back().map(Bytes::from);
+                    assert_eq!(listpack.pop_back(), expected);
+                }
+            }
+            if step % 100 == 0 {
+                check(&listpack, &model);
+            }
+        }
+        check(&listpack, &model);
+
+        let index = model.len() / 3;
+        let tail = listpack.split_off(index);
+        let model_tail = model.split_off(index);
+        check(&listpack, &model);
+        check(&tail, &model_tail);
+    }
+}
@@ -0,0 +1,14 @@
+mod hash;
+mod intset;
+mod list;
+mod listpack;
+mod set;
+mod skiplist;
+mod string;
+mod zset;
+
+pub use hash::Hash;
+pub use list::List;
+pub use set::Set;
+pub use string::{string_to_i64, StringValue};
+pub use zset::SortedSet;
@@ -0,0 +1,121 @@
+use bytes::Bytes;
+
+use super::{intset::Intset, string_to_i64};
+use crate::{dict::Dict, random::random_index};
+
+/// A set value. Sets whose members are all integers start out as an
+/// `Intset`, which moves to a `Dict` for good once a member is not an
+/// integer or the set grows past `max_intset_entries` members, following
+/// `set-max-intset-entries`.
+pub struct Set {
+    encoding: Encoding,
+    max_intset_entries: usize,
+}
+
+enum Encoding {
+    Intset(Intset),
+    Table(Dict<Bytes, ()>),
+}
+
+impl Set {
+    pub fn new(max_intset_entries: usize) -> Set {
+        Set {
+            encoding: Encoding::Intset(Intset::default()),
+            max_intset_entries,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.encoding {
+            Encoding::Intset(intset) => intset.len(),
+            Encoding::Table(table) => table.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match &self.encoding {
+            Encoding::Intset(intset) => string_to_i64(member).is_some_and(|n| intset.contains(n)),
+            Encoding::Table(table) => table.get(member).is_some(),
+        }
+    }
+
+    /// Adds `member`, returning whether it was not there yet.
+    pub fn insert(&mut self, member: &[u8]) -> bool {
+        if let Encoding::Intset(intset) = &mut self.encoding {
+            if let Some(n) = string_to_i64(member) {
+                if intset.contains(n) {
+                    return false;
+                }
+                if intset.len() < self.max_intset_entries {
+                    return intset.insert(n);
+                }
+            }
+            self.convert_to_table();
+        }
+
+        let Encoding::Table(table) = &mut self.encoding else {
+            unreachable!("intsets are handled above");
+        };
+        table.insert(Bytes::copy_from_slice(member), ()).is_none()
+    }
+
+    /// Removes `member`, returning whether it was there.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match &mut self.encoding {
+            Encoding::Intset(intset) => string_to_i64(member).is_some_and(|n| intset.remove(n)),
+            Encoding::Table(table) => table.remove(member).is_some(),
+        }
+    }
+
+    fn convert_to_table(&mut self) {
+        let Encoding::Intset(intset) = &self.encoding else {
+            return;
+        };
+        let mut table = Dict::default();
+        for n in intset.iter() {
+            table.insert(Bytes::from(n.to_string()), ());
+        }
+        self.encoding = Encoding::Table(table);
+    }
+
+    /// Iterates over the members, which an intset has to format on the way.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = Bytes> + '_> {
+        match &self.encoding {
+            Encoding::Intset(intset) => Box::new(intset.iter().map(|n| Bytes::from(n.to_string()))),
+            Encoding::Table(table) => Box::new(table.iter().map(|(member, _)| member.clone())),
+        }
+    }
+
+    pub fn random_member(&self) -> Option<Bytes> {
+        match &self.encoding {
+            Encoding::Intset(intset) if intset.is_empty() => None,
+            Encoding::Intset(intset) => intset
+                .get(random_index(intset.len()))
+                .map(|n| Bytes::from(n.to_string())),
+            Encoding::Table(table) => table.random_entry().map(|(member, _)| member.clone()),
+        }
+    }
+
+    /// Removes and returns a random member.
+    pub fn pop_random(&mut self) -> Option<Bytes> {
+        let member = self.random_member()?;
+        self.remove(&member);
+        Some(member)
+    }
+
+    /// Scans the members as [`Dict::scan`] does, except that an intset is
+    /// small enough to be visited in one go.
+    pub fn scan(&self, cursor: u64, count: usize, mut visit: impl FnMut(&[u8])) -> u64 {
+        match &self.encoding {
+            Encoding::Intset(intset) => {
+                intset.iter().for_each(|n| visit(n.to_string().as_bytes()));
+                0
+            }
+            Encoding::Table(table) => table.scan(cursor, count, |member, _| visit(member)),
+        }
+    }
+}
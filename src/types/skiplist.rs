@@ -0,0 +1,344 @@
+use std::cmp::Ordering;
+
+use bytes::Bytes;
+
+use crate::random::random_u64;
+
+const MAX_LEVEL: usize = 32;
+/// The head node, which holds no element.
+const HEAD: usize = 0;
+/// Marks the end of a level, or the lack of a previous node.
+const NIL: usize = usize::MAX;
+
+/// Elements ordered by score and then by member, as in Redis' zskiplist.
+///
+/// Each level of a node links to the next node at that level along with the
+/// number of elements the link skips (its span), so that besides finding an
+/// element, finding the rank of one or the element at a rank is O(log n).
+/// Nodes live in an arena and link to each other by index, with freed slots
+/// reused.
+pub struct Skiplist {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    level: usize,
+    len: usize,
+    tail: usize,
+}
+
+struct Node {
+    member: Bytes,
+    score: f64,
+    prev: usize,
+    links: Vec<Link>,
+}
+
+#[derive(Clone, Copy)]
+struct Link {
+    next: usize,
+    span: usize,
+}
+
+impl Default for Skiplist {
+    fn default() -> Skiplist {
+        let head = Node {
+            member: Bytes::new(),
+            score: 0.0,
+            prev: NIL,
+            links: vec![Link { next: NIL, span: 0 }; MAX_LEVEL],
+        };
+        Skiplist {
+            nodes: vec![head],
+            free: Vec::new(),
+            level: 1,
+            len: 0,
+            tail: NIL,
+        }
+    }
+}
+
+/// Orders elements by score, then by member.
+pub(super) fn compare(
+    score: f64,
+    member: &[u8],
+    other_score: f64,
+    other_member: &[u8],
+) -> Ordering {
+    score
+        .partial_cmp(&other_score)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| member.cmp(other_member))
+}
+
+/// A level for a new node: each level up is a quarter as likely.
+fn random_level() -> usize {
+    let mut level = 1;
+    while level < MAX_LEVEL && random_u64() & 3 == 0 {
+        level += 1;
+    }
+    level
+}
+
+impl Skiplist {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Finds, at every level, the last node ordered before `score` and
+    /// `member`, along with its rank (the head being rank 0).
+    fn predecessors(&self, score: f64, member: &[u8]) -> ([usize; MAX_LEVEL], [usize; MAX_LEVEL]) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i + 1 == self.level { 0 } else { rank[i + 1] };
+            loop {
+                let link = self.nodes[x].links[i];
+                if link.next == NIL {
+                    break;
+                }
+                let next = &self.nodes[link.next];
+                if compare(next.score, &next.member, score, member) != Ordering::Less {
+                    break;
+                }
+                rank[i] += link.span;
+                x = link.next;
+            }
+            update[i] = x;
+        }
+        (update, rank)
+    }
+
+    /// Inserts an element, whose member must not be in the list already.
+    pub fn insert(&mut self, member: Bytes, score: f64) {
+        let (mut update, mut rank) = self.predecessors(score, &member);
+        let level = random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].links[i].span = self.len;
+            }
+            self.level = level;
+        }
+
+        let node = Node {
+            member,
+            score,
+            prev: if update[0] == HEAD { NIL } else { update[0] },
+            links: vec![Link { next: NIL, span: 0 }; level],
+        };
+        let x = match self.free.pop() {
+            Some(x) => {
+                self.nodes[x] = node;
+                x
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        for i in 0..level {
+            let before = self.nodes[update[i]].links[i];
+            let skipped = rank[0] - rank[i];
+            self.nodes[x].links[i] = Link {
+                next: before.next,
+                span: before.span - skipped,
+            };
+            self.nodes[update[i]].links[i] = Link {
+                next: x,
+                span: skipped + 1,
+            };
+        }
+        for (i, &node) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[node].links[i].span += 1;
+        }
+
+        match self.nodes[x].links[0].next {
+            NIL => self.tail = x,
+            next => self.nodes[next].prev = x,
+        }
+        self.len += 1;
+    }
+
+    /// Removes the element with this score and member, returning whether it
+    /// was there.
+    pub fn remove(&mut self, score: f64, member: &[u8]) -> bool {
+        let (update, _) = self.predecessors(score, member);
+        let x = self.nodes[update[0]].links[0].next;
+        if x == NIL || self.nodes[x].score != score || self.nodes[x].member != member {
+            return false;
+        }
+
+        for (i, &node) in update.iter().enumerate().take(self.level) {
+            let removed = self.nodes[x].links.get(i).copied();
+            let link = &mut self.nodes[node].links[i];
+            match removed {
+                Some(removed) if link.next == x => {
+                    link.span = link.span + removed.span - 1;
+                    link.next = removed.next;
+                }
+                _ => link.span -= 1,
+            }
+        }
+        match self.nodes[x].links[0].next {
+            NIL => self.tail = self.nodes[x].prev,
+            next => self.nodes[next].prev = self.nodes[x].prev,
+        }
+        while self.level > 1 && self.nodes[HEAD].links[self.level - 1].next == NIL {
+            self.level -= 1;
+        }
+
+        self.nodes[x].member = Bytes::new();
+        self.nodes[x].links = Vec::new();
+        self.free.push(x);
+        self.len -= 1;
+        true
+    }
+
+    /// How many leading elements satisfy `pred`, which must hold for a
+    /// prefix of the list and not after it.
+    pub fn partition_point(&self, pred: impl Fn(f64, &[u8]) -> bool) -> usize {
+        let mut x = HEAD;
+        let mut rank = 0;
+        for i in (0..self.level).rev() {
+            loop {
+                let link = self.nodes[x].links[i];
+                if link.next == NIL {
+                    break;
+                }
+                let next = &self.nodes[link.next];
+                if !pred(next.score, &next.member) {
+                    break;
+                }
+                rank += link.span;
+                x = link.next;
+            }
+        }
+        rank
+    }
+
+    /// The node at 0-based `rank`.
+    fn node_at(&self, rank: usize) -> Option<usize> {
+        if rank >= self.len {
+            return None;
+        }
+        if rank + 1 == self.len {
+            return Some(self.tail);
+        }
+        let target = rank + 1;
+        let mut x = HEAD;
+        let mut traversed = 0;
+        for i in (0..self.level).rev() {
+            loop {
+                let link = self.nodes[x].links[i];
+                if link.next == NIL || traversed + link.span > target {
+                    break;
+                }
+                traversed += link.span;
+                x = link.next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// Iterates from the element at `rank`, towards the tail, or towards
+    /// the head if `rev` is set.
+    pub fn iter_from(&self, rank: usize, rev: bool) -> Iter<'_> {
+        Iter {
+            list: self,
+            node: self.node_at(rank).unwrap_or(NIL),
+            rev,
+        }
+    }
+}
+
+pub struct Iter<'a> {
+    list: &'a Skiplist,
+    node: usize,
+    rev: bool,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a [u8], f64);
+
+    fn next(&mut self) -> Option<(&'a [u8], f64)> {
+        let node = self.list.nodes.get(self.node)?;
+        self.node = if self.rev {
+            node.prev
+        } else {
+            node.links[0].next
+        };
+        Some((&node.member, node.score))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::random_index;
+
+    /// Checks the list against `model`, its elements in order.
+    fn check(list: &Skiplist, model: &[(f64, Bytes)]) {
+        assert_eq!(list.len(), model.len());
+        for rank in 0..=model.len() {
+            let forward: Vec<_> = list
+                .iter_from(rank, false)
+                .map(|(member, score)| (score, Bytes::copy_from_slice(member)))
+                .collect();
+            assert_eq!(forward, model.get(rank..).unwrap_or_default());
+
+            let backward: Vec<_> = list
+                .iter_from(rank, true)
+                .map(|(member, score)| (score, Bytes::copy_from_slice(member)))
+                .collect();
+            let expected: Vec<_> = model.iter().take(rank + 1).rev().cloned().collect();
+            if rank < model.len() {
+                assert_eq!(backward, expected);
+            } else {
+                assert!(backward.is_empty());
+            }
+        }
+        for (rank, (score, member)) in model.iter().enumerate() {
+            let before = list.partition_point(|other_score, other| {
+                compare(other_score, other, *score, member) == Ordering::Less
+            });
+            assert_eq!(before, rank);
+        }
+    }
+
+    #[test]
+    fn random_inserts_and_removes_keep_ranks_and_order() {
+        let mut list = Skiplist::default();
+        let mut model: Vec<(f64, Bytes)> = Vec::new();
+        for step in 0..3000 {
+            let member = Bytes::from(format!("m{}", random_index(150)));
+            let score = random_index(11) as f64 - 5.0;
+            match model.iter().position(|(_, other)| *other == member) {
+                Some(index) => {
+                    let (old_score, _) = model.remove(index);
+                    assert!(!list.remove(old_score + 0.5, &member));
+                    assert!(list.remove(old_score, &member));
+                }
+                None => {
+                    list.insert(member.clone(), score);
+                    model.push((score, member));
+                    model.sort_by(|(a, x), (b, y)| compare(*a, x, *b, y));
+                }
+            }
+            if step % 100 == 0 {
+                check(&list, &model);
+            }
+        }
+        check(&list, &model);
+
+        for (score, member) in std::mem::take(&mut model) {
+            assert!(list.remove(score, &member));
+        }
+        check(&list, &model);
+        assert_eq!(list.level, 1);
+    }
+}
@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+
+use bytes::Bytes;
+
+/// Longest decimal representation of an `i64`, sign included.
+const MAX_INT_LENGTH: usize = 20;
+
+/// A string value. Strings that are the canonical decimal form of an `i64`
+/// are kept as the number itself, which is smaller and spares counters a
+/// parse on every increment. Strings are otherwise kept as the bytes they
+/// arrived in until something edits them in place, after which they live in
+/// their own growable buffer so repeated appends stay cheap.
+#[derive(Clone)]
+pub enum StringValue {
+    Int(i64),
+    Raw(Bytes),
+    Buffer(Vec<u8>),
+}
+
+impl StringValue {
+    /// Wraps `bytes`, choosing the integer encoding when it round-trips.
+    pub fn from_bytes(bytes: Bytes) -> StringValue {
+        if bytes.len() <= MAX_INT_LENGTH {
+            if let Some(n) = string_to_i64(&bytes) {
+                return StringValue::Int(n);
+            }
+        }
+        StringValue::Raw(bytes)
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            StringValue::Int(n) => Bytes::from(n.to_string()),
+            StringValue::Raw(bytes) => bytes.clone(),
+            StringValue::Buffer(buffer) => Bytes::copy_from_slice(buffer),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Cow<'_, [u8]> {
+        match self {
+            StringValue::Int(n) => Cow::Owned(n.to_string().into_bytes()),
+            StringValue::Raw(bytes) => Cow::Borrowed(bytes),
+            StringValue::Buffer(buffer) => Cow::Borrowed(buffer),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            StringValue::Int(n) => {
+                let digits = n
+                    .unsigned_abs()
+                    .checked_ilog10()
+                    .map_or(1, |log| log as usize + 1);
+                digits + usize::from(*n < 0)
+            }
+            StringValue::Raw(bytes) => bytes.len(),
+            StringValue::Buffer(buffer) => buffer.len(),
+        }
+    }
+
+    /// The value as an integer, if it is one.
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            StringValue::Int(n) => Some(*n),
+            StringValue::Raw(bytes) => string_to_i64(bytes),
+            StringValue::Buffer(buffer) => string_to_i64(buffer),
+        }
+    }
+
+    /// The value as a buffer that can be edited in place, switching to the
+    /// buffer encoding first if needed.
+    pub fn make_mut(&mut self) -> &mut Vec<u8> {
+        if !matches!(self, StringValue::Buffer(_)) {
+            *self = StringValue::Buffer(self.as_bytes().into_owned());
+        }
+        match self {
+            StringValue::Buffer(buffer) => buffer,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Parses `bytes` as an integer as strictly as Redis' `string2ll`: no sign
+/// other than a leading `-`, no leading zeros and no surrounding spaces, so
+/// that only strings which format back to exactly the same bytes are accepted.
+pub fn string_to_i64(bytes: &[u8]) -> Option<i64> {
+    if !matches!(bytes, [b'0'] | [b'1'..=b'9', ..] | [b'-', b'1'..=b'9', ..]) {
+        return None;
+    }
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn int_length_matches_its_decimal_form() {
+        for n in [0, 1, 9, 10, 99, 100, -1, -9, -10, i64::MAX, i64::MIN] {
+            assert_eq!(StringValue::Int(n).len(), n.to_string().len(), "{n}");
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::{cmp::Ordering, ops::Range};
+
+use bytes::Bytes;
+
+use super::{
+    listpack::Listpack,
+    skiplist::{compare, Skiplist},
+};
+use crate::{dict::Dict, random::random_index};
+
+/// A sorted set value: members ordered by score, then by member.
+///
+/// Small sorted sets are a listpack of alternating members and scores kept
+/// in order, which makes every operation a scan. Once one has more than
+/// `max_listpack_entries` members or a member longer than
+/// `max_listpack_value` bytes, following `zset-max-listpack-entries` and
+/// `zset-max-listpack-value`, it moves for good to a skiplist for ordered
+/// access plus a `Dict` from member to score, as in Redis.
+pub struct SortedSet {
+    encoding: Encoding,
+    max_listpack_entries: usize,
+    max_listpack_value: usize,
+}
+
+enum Encoding {
+    Listpack(Listpack),
+    Skiplist {
+        scores: Dict<Bytes, f64>,
+        list: Skiplist,
+    },
+}
+
+/// Listpacks store scores as their bit pattern, which unlike a decimal form
+/// round-trips exactly.
+fn encode_score(score: f64) -> [u8; 8] {
+    score.to_bits().to_le_bytes()
+}
+
+fn decode_score(bytes: &[u8]) -> f64 {
+    f64::from_bits(u64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+}
+
+impl SortedSet {
+    pub fn new(max_listpack_entries: usize, max_listpack_value: usize) -> SortedSet {
+        SortedSet {
+            encoding: Encoding::Listpack(Listpack::default()),
+            max_listpack_entries,
+            max_listpack_value,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => listpack.len() / 2,
+            Encoding::Skiplist { list, .. } => list.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => listpack
+                .pairs()
+                .find(|(candidate, _)| *candidate == member)
+                .map(|(_, score)| decode_score(score)),
+            Encoding::Skiplist { scores, .. } => scores.get(member).copied(),
+        }
+    }
+
+    /// Adds `member` with `score`, or moves it to `score` if it is already
+    /// there. Returns whether the member is new.
+    pub fn insert(&mut self, member: &[u8], score: f64) -> bool {
+        if member.len() > self.max_listpack_value {
+            self.convert_to_skiplist();
+        }
+
+        if let Encoding::Listpack(listpack) = &mut self.encoding {
+            let existing = listpack
+                .pairs()
+                .position(|(candidate, _)| candidate == member);
+            if let Some(index) = existing {
+                listpack.remove_range(index * 2, 2);
+            }
+            if existing.is_some() || listpack.len() / 2 < self.max_listpack_entries {
+                let index = listpack
+                    .pairs()
+                    .take_while(|(other, other_score)| {
+                        compare(decode_score(other_score), other, score, member) == Ordering::Less
+                    })
+                    .count();
+                listpack.insert(index * 2, member);
+                listpack.insert(index * 2 + 1, &encode_score(score));
+                return existing.is_none();
+            }
+            self.convert_to_skiplist();
+        }
+
+        let Encoding::Skiplist { scores, list } = &mut self.encoding else {
+            unreachable!("listpack sorted sets are handled above");
+        };
+        let member = Bytes::copy_from_slice(member);
+        let old = scores.insert(member.clone(), score);
+        if let Some(old) = old {
+            list.remove(old, &member);
+        }
+        list.insert(member, score);
+        old.is_none()
+    }
+
+    /// Removes `member`, returning whether it was there.
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match &mut self.encoding {
+            Encoding::Listpack(listpack) => {
+                let Some(index) = listpack
+                    .pairs()
+                    .position(|(candidate, _)| candidate == member)
+                else {
+                    return false;
+                };
+                listpack.remove_range(index * 2, 2);
+                true
+            }
+            Encoding::Skiplist { scores, list } => match scores.remove(member) {
+                Some(score) => list.remove(score, member),
+                None => false,
+            },
+        }
+    }
+
+    /// Removes the elements whose ranks fall in `ranks`, returning how many
+    /// there were.
+    pub fn remove_range(&mut self, ranks: Range<usize>) -> usize {
+        let ranks = ranks.start..ranks.end.min(self.len());
+        if ranks.is_empty() {
+            return 0;
+        }
+        match &mut self.encoding {
+            Encoding::Listpack(listpack) => listpack.remove_range(ranks.start * 2, ranks.len() * 2),
+            Encoding::Skiplist { scores, list } => {
+                let removed: Vec<(Bytes, f64)> = list
+                    .iter_from(ranks.start, false)
+                    .take(ranks.len())
+                    .map(|(member, score)| (Bytes::copy_from_slice(member), score))
+                    .collect();
+                for (member, score) in removed {
+                    scores.remove(&member);
+                    list.remove(score, &member);
+                }
+            }
+        }
+        ranks.len()
+    }
+
+    fn convert_to_skiplist(&mut self) {
+        let Encoding::Listpack(listpack) = &self.encoding else {
+            return;
+        };
+        let mut scores = Dict::default();
+        let mut list = Skiplist::default();
+        for (member, score) in listpack.pairs() {
+            let member = Bytes::copy_from_slice(member);
+            let score = decode_score(score);
+            scores.insert(member.clone(), score);
+            list.insert(member, score);
+        }
+        self.encoding = Encoding::Skiplist { scores, list };
+    }
+
+    /// The 0-based rank of `member` in ascending order.
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => listpack
+                .pairs()
+                .position(|(candidate, _)| candidate == member),
+            Encoding::Skiplist { scores, list } => {
+                let score = *scores.get(member)?;
+                Some(list.partition_point(|other_score, other| {
+                    compare(other_score, other, score, member) == Ordering::Less
+                }))
+            }
+        }
+    }
+
+    /// How many leading elements satisfy `pred`, which must hold for a
+    /// prefix of the set and not after it. This is how score and lex
+    /// ranges are turned into ranks.
+    pub fn partition_point(&self, pred: impl Fn(f64, &[u8]) -> bool) -> usize {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => listpack
+                .pairs()
+                .take_while(|(member, score)| pred(decode_score(score), member))
+                .count(),
+            Encoding::Skiplist { list, .. } => list.partition_point(pred),
+        }
+    }
+
+    /// Iterates from the element at `rank` towards the highest score, or
+    /// towards the lowest if `rev` is set.
+    pub fn iter_from(&self, rank: usize, rev: bool) -> Box<dyn Iterator<Item = (&[u8], f64)> + '_> {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => {
+                let elements = listpack
+                    .pairs()
+                    .map(|(member, score)| (member, decode_score(score)));
+                if !rev {
+                    Box::new(elements.skip(rank))
+                } else if rank >= self.len() {
+                    Box::new(std::iter::empty())
+                } else {
+                    let elements: Vec<_> = elements.take(rank + 1).collect();
+                    Box::new(elements.into_iter().rev())
+                }
+            }
+            Encoding::Skiplist { list, .. } => Box::new(list.iter_from(rank, rev)),
+        }
+    }
+
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (&[u8], f64)> + '_> {
+        self.iter_from(0, false)
+    }
+
+    pub fn random_entry(&self) -> Option<(&[u8], f64)> {
+        match &self.encoding {
+            Encoding::Listpack(listpack) if listpack.is_empty() => None,
+            Encoding::Listpack(listpack) => {
+                let index = random_index(listpack.len() / 2) * 2;
+                Some((listpack.get(index)?, decode_score(listpack.get(index + 1)?)))
+            }
+            Encoding::Skiplist { scores, .. } => scores
+                .random_entry()
+                .map(|(member, &score)| (&member[..], score)),
+        }
+    }
+
+    /// Scans the members and their scores through the member-to-score
+    /// table, as [`Dict::scan`] does. Listpacks are visited whole.
+    pub fn scan(&self, cursor: u64, count: usize, mut visit: impl FnMut(&[u8], f64)) -> u64 {
+        match &self.encoding {
+            Encoding::Listpack(listpack) => {
+                listpack
+                    .pairs()
+                    .for_each(|(member, score)| visit(member, decode_score(score)));
+                0
+            }
+            Encoding::Skiplist { scores, .. } => {
+                scores.scan(cursor, count, |member, &score| visit(member, score))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn sorted(model: &HashMap<Bytes, f64>) -> Vec<(Bytes, f64)> {
+        let mut elements: Vec<_> = model.iter().map(|(m, &s)| (m.clone(), s)).collect();
+        elements.sort_by(|(x, a), (y, b)| compare(*a, x, *b, y));
+        elements
+    }
+
+    fn collect(elements: Box<dyn Iterator<Item = (&[u8], f64)> + '_>) -> Vec<(Bytes, f64)> {
+        elements
+            .map(|(member, score)| (Bytes::copy_from_slice(member), score))
+            .collect()
+    }
+
+    /// Checks every query against `model`.
+    fn check(zset: &SortedSet, model: &HashMap<Bytes, f64>) {
+        let expected = sorted(model);
+        assert_eq!(zset.len(), expected.len());
+        assert_eq!(collect(zset.iter()), expected);
+        for (rank, (member, score)) in expected.iter().enumerate() {
+            assert_eq!(zset.score(member), Some(*score));
+            assert_eq!(zset.rank(member), Some(rank));
+            assert_eq!(collect(zset.iter_from(rank, false)), expected[rank..]);
+            let backward: Vec<_> = expected[..=rank].iter().rev().cloned().collect();
+            assert_eq!(collect(zset.iter_from(rank, true)), backward);
+        }
+        assert!(collect(zset.iter_from(expected.len(), false)).is_empty());
+        assert!(collect(zset.iter_from(expected.len(), true)).is_empty());
+        for bound in -6..=6 {
+            let bound = bound as f64;
+            let below = expected.iter().filter(|(_, score)| *score < bound).count();
+            assert_eq!(zset.partition_point(|score, _| score < bound), below);
+        }
+    }
+
+    fn is_listpack(zset: &SortedSet) -> bool {
+        matches!(zset.encoding, Encoding::Listpack(_))
+    }
+
+    /// Runs random inserts, updates, removals and range removals against a
+    /// model, for a set that stays a listpack, one that converts part of the
+    /// way through and one that is a skiplist almost from the start.
+    #[test]
+    fn random_operations_match_a_sorted_model() {
+        for max_listpack_entries in [1000, 40, 1] {
+            let mut zset = SortedSet::new(max_listpack_entries, 64);
+            let mut model = HashMap::new();
+            for step in 0..3000 {
+                let member = Bytes::from(format!("m{}", random_index(80)));
+                let score = random_index(11) as f64 - 5.0;
+                match random_index(10) {
+                    0..=5 => {
+                        let new = model.insert(member.clone(), score).is_none();
+                        assert_eq!(zset.insert(&member, score), new);
+                    }
+                    6..=8 => assert_eq!(zset.remove(&member), model.remove(&member).is_some()),
+                    _ => {
+                        let expected = sorted(&model);
+                        let start = random_index(expected.len() + 1);
+                        let end = start + random_index(4);
+                        let removed = expected
+                            .get(start..end.min(expected.len()))
+                            .unwrap_or_default();
+                        for (member, _) in removed {
+                            model.remove(member);
+                        }
+                        assert_eq!(zset.remove_range(start..end), removed.len());
+                    }
+                }
+                if model.len() > max_listpack_entries {
+                    assert!(!is_listpack(&zset));
+                }
+                if step % 100 == 0 {
+                    check(&zset, &model);
+                }
+            }
+            check(&zset, &model);
+            assert_eq!(is_listpack(&zset), max_listpack_entries == 1000);
+        }
+    }
+
+    #[test]
+    fn converts_past_the_listpack_limits_and_keeps_contents() {
+        let mut zset = SortedSet::new(4, 8);
+        let mut model = HashMap::new();
+        for (i, member) in ["d", "c", "b", "a"].into_iter().enumerate() {
+            zset.insert(member.as_bytes(), i as f64);
+            model.insert(Bytes::from(member), i as f64);
+        }
+        // Updating a member at the limit does not convert.
+        zset.insert(b"a", -1.0);
+        model.insert(Bytes::from("a"), -1.0);
+        assert!(is_listpack(&zset));
+        check(&zset, &model);
+
+        zset.insert(b"e", 2.0);
+        model.insert(Bytes::from("e"), 2.0);
+        assert!(!is_listpack(&zset));
+        check(&zset, &model);
+
+        let mut zset = SortedSet::new(4, 8);
+        zset.insert(b"a", 1.0);
+        zset.insert(b"a-long-member", 0.0);
+        assert!(!is_listpack(&zset));
+        assert_eq!(zset.rank(b"a-long-member"), Some(0));
+        assert_eq!(zset.rank(b"a"), Some(1));
+    }
+}